@@ -0,0 +1,645 @@
+use final_trps::{
+    communicator::CommSettings,
+    project::Project,
+    rule_data::{ArgData, ContentType},
+    run_manager::{
+        self, FailureReport, LaunchSettings, RunManager, TestReport, TestingData, Wrapper,
+    },
+    stats::RunStats,
+};
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::atomic::Ordering,
+    thread,
+    time::Duration,
+};
+
+pub const USAGE: &str = "\
+Использование:
+    final-trps                        запустить графический интерфейс
+    final-trps run <проект> [опции]   провести тестирование без интерфейса
+
+Параметры запуска, ограничения и обёртка берутся из файла проекта, опции их заменяют.
+
+Опции:
+    --program <путь>      тестируемая программа (по умолчанию - из файла проекта)
+    --arg <текст>         аргумент командной строки программы; опцию можно указать несколько раз,
+                          аргументы из файла проекта при этом не используются
+    --env <имя>=<значение>
+                          переменная окружения программы; опцию можно указать несколько раз
+    --clear-env           не передавать программе текущее окружение
+    --working-dir <путь>  рабочая папка программы
+    --iterations <N>      требуемое количество успешных тестов (по умолчанию - из файла проекта,
+                          а при указании --time - без ограничения)
+    --time <секунды>      не начинать новых запусков по истечении этого времени
+    --read-timeout <мс>   наибольшее ожидание строки вывода
+    --run-timeout <мс>    наибольшее время одного запуска программы
+    --max-duration <мс>   считать ошибкой запуски, работающие дольше
+    --max-memory <МиБ>    считать ошибкой запуски, занявшие больше памяти (только Unix)
+    --max-cpu <мс>        считать ошибкой запуски, занявшие больше времени процессора (только Unix)
+    --max-line <байт>     наибольшая длина строки вывода
+    --max-output <МиБ>    наибольший объём вывода за запуск
+    --max-lines <N>       наибольшее количество строк вывода за запуск
+    --terminal            запускать программу в псевдотерминале (только Linux)
+    --wrapper <команда>   запускать программу через команду-обёртку, например
+                          \"valgrind --error-exitcode=99\" (путь и аргументы программы - в конце);
+                          слова разделяются пробелами, кавычки не поддерживаются; системные
+                          ограничения из файла проекта действуют и на обёртку, кроме
+                          адресного пространства
+    --wrapper-code <код>=<описание>
+                          считать код завершения обёртки отдельным видом ошибки, например
+                          \"99=Ошибка памяти (valgrind)\"; опцию можно указать несколько раз,
+                          коды обёртки из файла проекта сохраняются
+    --wrapper-limit-memory
+                          ограничивать адресное пространство и для обёртки (не подходит для valgrind)
+    --workers <N>         количество одновременных запусков (по умолчанию - по числу ядер)
+    --keep-going          не останавливаться на первой ошибке, собрать все
+    --seed <N>            начальное зерно случайной генерации
+    --replay <N>          повторить единственный запуск с данным зерном
+
+Коды завершения: 0 - все тесты прошли успешно, 1 - обнаружены ошибки, 2 - ошибка выполнения";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CliArgs {
+    project_file: PathBuf,
+    program_path: Option<PathBuf>,
+    /// Replace the arguments of the project if there are any.
+    program_args: Vec<ArgData>,
+    env: Vec<(String, String)>,
+    clear_env: bool,
+    working_dir: Option<PathBuf>,
+    iterations: Option<u32>,
+    workers: Option<u32>,
+    time_budget: Option<Duration>,
+    read_timeout: Option<Duration>,
+    run_timeout: Option<Duration>,
+    max_run_duration: Option<Duration>,
+    max_peak_memory: Option<u64>,
+    max_cpu_time: Option<Duration>,
+    max_line_len: Option<u64>,
+    max_output_len: Option<u64>,
+    max_output_lines: Option<u64>,
+    terminal: bool,
+    /// Replaces the command of the project's wrapper, see [`CliArgs::wrapper`].
+    wrapper_command: Option<Vec<String>>,
+    wrapper_codes: Vec<(i32, String)>,
+    wrapper_limit_memory: bool,
+    keep_going: bool,
+    master_seed: Option<u64>,
+    replay_seed: Option<u64>,
+}
+
+impl CliArgs {
+    /// `args` don't include the program name and the `run` subcommand.
+    fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut project_file = None;
+        let mut this = Self::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow::format_err!("не указано значение опции {}", arg))
+            };
+
+            match arg.as_str() {
+                "--program" => this.program_path = Some(value()?.into()),
+                "--arg" => this.program_args.push(ArgData {
+                    content_type: ContentType::PlainText,
+                    text: value()?.to_owned(),
+                }),
+                "--env" => this.env.push(parse_env(value()?)?),
+                "--clear-env" => this.clear_env = true,
+                "--working-dir" => this.working_dir = Some(value()?.into()),
+                "--iterations" => {
+                    this.iterations = Some(parse_positive(
+                        value()?,
+                        "количество тестов должно быть положительным целым числом",
+                    )?);
+                }
+                "--workers" => {
+                    this.workers = Some(parse_positive(
+                        value()?,
+                        "количество запусков должно быть положительным целым числом",
+                    )?);
+                }
+                "--time" => {
+                    this.time_budget = Some(Duration::from_secs(parse_positive(
+                        value()?,
+                        "время тестирования должно быть положительным целым числом секунд",
+                    )?));
+                }
+                "--read-timeout" => {
+                    this.read_timeout = Some(Duration::from_millis(parse_positive(
+                        value()?,
+                        "время ожидания должно быть положительным целым числом миллисекунд",
+                    )?));
+                }
+                "--run-timeout" => {
+                    this.run_timeout = Some(Duration::from_millis(parse_positive(
+                        value()?,
+                        "время запуска должно быть положительным целым числом миллисекунд",
+                    )?));
+                }
+                "--max-duration" => {
+                    this.max_run_duration = Some(Duration::from_millis(parse_positive(
+                        value()?,
+                        "время работы должно быть положительным целым числом миллисекунд",
+                    )?));
+                }
+                "--max-memory" => {
+                    this.max_peak_memory = Some(parse_mebibytes(
+                        value()?,
+                        "объём памяти должен быть положительным целым числом мебибайт",
+                    )?);
+                }
+                "--max-cpu" => {
+                    this.max_cpu_time = Some(Duration::from_millis(parse_positive(
+                        value()?,
+                        "время процессора должно быть положительным целым числом миллисекунд",
+                    )?));
+                }
+                "--max-line" => {
+                    this.max_line_len = Some(parse_positive(
+                        value()?,
+                        "длина строки должна быть положительным целым числом байт",
+                    )?);
+                }
+                "--max-output" => {
+                    this.max_output_len = Some(parse_mebibytes(
+                        value()?,
+                        "объём вывода должен быть положительным целым числом мебибайт",
+                    )?);
+                }
+                "--max-lines" => {
+                    this.max_output_lines = Some(parse_positive(
+                        value()?,
+                        "количество строк должно быть положительным целым числом",
+                    )?);
+                }
+                "--terminal" => this.terminal = true,
+                "--wrapper" => {
+                    let command = Wrapper::split_command(value()?);
+
+                    if command.is_empty() {
+                        anyhow::bail!("команда-обёртка не может быть пустой");
+                    }
+
+                    this.wrapper_command = Some(command);
+                }
+                "--wrapper-code" => this.wrapper_codes.push(parse_wrapper_code(value()?)?),
+                "--wrapper-limit-memory" => this.wrapper_limit_memory = true,
+                "--keep-going" => this.keep_going = true,
+                "--seed" => this.master_seed = Some(parse_seed(value()?)?),
+                "--replay" => this.replay_seed = Some(parse_seed(value()?)?),
+                option if option.starts_with("--") => {
+                    anyhow::bail!("неизвестная опция {}", option)
+                }
+                path if project_file.is_none() => project_file = Some(PathBuf::from(path)),
+                extra => anyhow::bail!("лишний аргумент {}", extra),
+            }
+        }
+
+        this.project_file =
+            project_file.ok_or_else(|| anyhow::format_err!("не указан файл проекта"))?;
+
+        Ok(this)
+    }
+
+    /// The project's wrapper with the options applied. The exit codes given
+    /// here are added to the project's ones and win over them.
+    fn wrapper(&self, project: Option<Wrapper>) -> anyhow::Result<Option<Wrapper>> {
+        let mut wrapper = match (self.wrapper_command.clone(), project) {
+            (Some(command), project) => Wrapper {
+                command,
+                ..project.unwrap_or_default()
+            },
+            (None, Some(project)) => project,
+            (None, None) if !self.wrapper_codes.is_empty() => {
+                anyhow::bail!(
+                    "коды завершения обёртки указаны, но обёртки нет ни в проекте, ни в опциях"
+                )
+            }
+            (None, None) if self.wrapper_limit_memory => {
+                anyhow::bail!(
+                    "ограничение памяти обёртки указано, но обёртки нет ни в проекте, ни в опциях"
+                )
+            }
+            (None, None) => return Ok(None),
+        };
+
+        wrapper
+            .exit_codes
+            .retain(|(code, _)| self.wrapper_codes.iter().all(|(other, _)| other != code));
+        wrapper
+            .exit_codes
+            .extend(self.wrapper_codes.iter().cloned());
+        wrapper.limit_address_space |= self.wrapper_limit_memory;
+
+        Ok(Some(wrapper))
+    }
+}
+
+#[inline]
+fn parse_positive<T: FromStr + Default + PartialOrd>(text: &str, error: &str) -> anyhow::Result<T> {
+    text.parse()
+        .ok()
+        .filter(|value| *value > T::default())
+        .ok_or_else(|| anyhow::format_err!("{}", error))
+}
+
+/// Returns bytes.
+#[inline]
+fn parse_mebibytes(text: &str, error: &str) -> anyhow::Result<u64> {
+    parse_positive::<u64>(text, error)?
+        .checked_mul(1 << 20)
+        .ok_or_else(|| anyhow::format_err!("слишком большой объём: {} МиБ", text))
+}
+
+/// `LANG=C`, the value may be empty.
+fn parse_env(text: &str) -> anyhow::Result<(String, String)> {
+    match text.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => anyhow::bail!("ожидалось «имя=значение», получено «{}»", text),
+    }
+}
+
+/// `99=Ошибка памяти (valgrind)`.
+fn parse_wrapper_code(text: &str) -> anyhow::Result<(i32, String)> {
+    let error = || anyhow::format_err!("ожидалось «код=описание», получено «{}»", text);
+
+    let (code, category) = text.split_once('=').ok_or_else(error)?;
+    let code = code.trim().parse().map_err(|_| error())?;
+
+    match category.trim() {
+        "" => Err(error()),
+        category => Ok((code, category.to_owned())),
+    }
+}
+
+#[inline]
+fn parse_seed(text: &str) -> anyhow::Result<u64> {
+    text.parse().map_err(|_| {
+        anyhow::format_err!("зерно должно быть целым числом от 0 до 18446744073709551615")
+    })
+}
+
+/// Runs the command line mode and returns the exit code.
+pub fn run(args: &[String]) -> i32 {
+    match args.split_first() {
+        Some((command, rest)) if command == "run" => match run_tests(rest) {
+            Ok(true) => 0,
+            Ok(false) => 1,
+            Err(error) => {
+                eprintln!("Ошибка: {error:#}");
+                2
+            }
+        },
+        Some((help, _)) if help == "--help" || help == "-h" => {
+            println!("{USAGE}");
+            0
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            2
+        }
+    }
+}
+
+/// Returns `true` if all tests passed.
+fn run_tests(args: &[String]) -> anyhow::Result<bool> {
+    let args = CliArgs::parse(args)?;
+    let project = Project::load(&args.project_file)?;
+    let (comm, mut launch) = (project.comm_settings, project.launch);
+    let wrapper = args.wrapper(launch.wrapper.take())?;
+
+    let testing_data = TestingData {
+        program_path: args.program_path.or(project.program_path).ok_or_else(|| {
+            anyhow::format_err!("тестируемая программа не указана ни в проекте, ни в опциях")
+        })?,
+        rules: project.rules,
+        successes_required: match (args.iterations, args.time_budget) {
+            (Some(iterations), _) => iterations,
+            (None, Some(_)) => u32::MAX,
+            (None, None) => project.successes_required,
+        },
+        workers: args.workers.unwrap_or_else(run_manager::default_workers),
+        continue_on_failure: args.keep_going,
+        time_budget: args.time_budget,
+        max_run_duration: args.max_run_duration.or(project.max_run_duration),
+        max_peak_memory: args.max_peak_memory.or(project.max_peak_memory),
+        max_cpu_time: args.max_cpu_time.or(project.max_cpu_time),
+        comm_settings: CommSettings {
+            read_timeout: args.read_timeout.or(comm.read_timeout),
+            run_timeout: args.run_timeout.or(comm.run_timeout),
+            max_line_len: args.max_line_len.or(comm.max_line_len),
+            max_output_len: args.max_output_len.or(comm.max_output_len),
+            max_output_lines: args.max_output_lines.or(comm.max_output_lines),
+            terminal: args.terminal || comm.terminal,
+            ..comm
+        },
+        launch: LaunchSettings {
+            args: match args.program_args.is_empty() {
+                true => launch.args,
+                false => args.program_args,
+            },
+            env: launch.env.into_iter().chain(args.env).collect(),
+            clear_env: args.clear_env || launch.clear_env,
+            working_dir: args.working_dir.or(launch.working_dir),
+            limits: launch.limits,
+            wrapper,
+        },
+        master_seed: args.master_seed,
+        replay_seed: args.replay_seed,
+    };
+
+    if testing_data.launch.effective_limits() != testing_data.launch.limits {
+        eprintln!(
+            "Предупреждение: адресное пространство не ограничивается при запуске через обёртку (см. --wrapper-limit-memory)"
+        );
+    }
+
+    if testing_data.rules.is_empty() {
+        anyhow::bail!("в проекте нет ни одного правила");
+    }
+
+    let mut run_manager = RunManager::create_and_start_thread();
+
+    if !run_manager.send_testing_data(testing_data) {
+        anyhow::bail!("не удалось запустить тестирование");
+    }
+
+    let mut progress_shown = false;
+
+    loop {
+        match run_manager.try_receive_result() {
+            Some(true) => break,
+            Some(false) => {}
+            None => anyhow::bail!("поток тестирования аварийно завершился"),
+        }
+
+        let work_state = &run_manager.work_state;
+        let tests_required = work_state.required_tests.load(Ordering::Acquire);
+
+        if tests_required > 0 {
+            let runs = match tests_required {
+                u32::MAX => work_state.finished_runs().to_string(),
+                _ => format!("{}/{}", work_state.finished_runs(), tests_required),
+            };
+            let elapsed = work_state.elapsed().as_secs();
+            let time = match args.time_budget {
+                Some(budget) => format!("{}/{} с", elapsed, budget.as_secs()),
+                None => format!("{} с", elapsed),
+            };
+
+            // the trailing spaces clear what's left of a longer line
+            eprint!(
+                "\rПрогресс: {}, время: {}, запусков в секунду: {:.1}   ",
+                runs,
+                time,
+                work_state.throughput()
+            );
+            let _ = io::stderr().flush();
+
+            progress_shown = true;
+        }
+
+        thread::sleep(PROGRESS_INTERVAL);
+    }
+
+    if progress_shown {
+        eprintln!();
+    }
+
+    match run_manager.last_report.take() {
+        Some(TestReport::Success(run_stats)) => {
+            println!("Все тесты прошли успешно");
+            print_stats(&run_stats);
+
+            Ok(true)
+        }
+        Some(TestReport::Failure(failure, run_stats)) => {
+            println!("Обнаружены ошибки:");
+            print_failure(&failure);
+            print_stats(&run_stats);
+
+            Ok(false)
+        }
+        Some(TestReport::Summary(summary)) => {
+            println!("Успешно: {}", summary.passed);
+            println!("Ошибки: {}", summary.failed);
+            println!("Аварийные завершения: {}", summary.crashed);
+            println!("Превышения времени: {}", summary.timed_out);
+
+            for (i, group) in summary.groups.iter().enumerate() {
+                let failure = &group.representative;
+
+                println!("\n#== Ошибка №{} ({}) ==#", i + 1, failure.kind);
+                println!("Признак: {}", failure.signature);
+                println!("Повторений: {}", group.count);
+                print_failure(failure);
+            }
+
+            print_stats(&summary.run_stats);
+
+            Ok(summary.groups.is_empty())
+        }
+        Some(TestReport::Error(error)) => Err(error),
+        None => anyhow::bail!("результат тестирования не получен"),
+    }
+}
+
+fn print_failure(failure: &FailureReport) {
+    println!("История ввода/вывода:\n{}", failure.history);
+    println!("{}", failure.error_message);
+    println!("Зерно запуска: {}", failure.seed);
+
+    if let Some(usage) = failure.usage.as_ref() {
+        println!("Ресурсы: {}", usage);
+    }
+
+    if let Some(minimized) = failure.minimized.as_ref() {
+        println!("\nМинимизированный пример:\n{}", minimized.history);
+        println!("{}", minimized.error_message);
+    }
+}
+
+fn print_stats(run_stats: &RunStats) {
+    if *run_stats != RunStats::default() {
+        print!("\n{}", run_stats);
+    }
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+//===================================================================================//
+//===================================// TESTING //===================================//
+//===================================================================================//
+
+#[cfg(test)]
+mod test_cli_args {
+    use super::{ArgData, CliArgs, ContentType, Wrapper};
+    use std::time::Duration;
+
+    fn parse(args: &str) -> anyhow::Result<CliArgs> {
+        let args: Vec<String> = args.split_whitespace().map(str::to_owned).collect();
+
+        CliArgs::parse(&args)
+    }
+
+    #[test]
+    fn project_only() {
+        assert_eq!(
+            parse("tests.trps").unwrap(),
+            CliArgs {
+                project_file: "tests.trps".into(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn all_options() {
+        assert_eq!(
+            parse(
+                "--iterations 500 tests.trps --program ./prog --arg -v --arg 2 --env LANG=C --env EMPTY= --clear-env --working-dir /tmp --workers 4 --time 600 --read-timeout 1500 --run-timeout 9000 --max-duration 250 --max-memory 64 --max-cpu 100 --max-line 4096 --max-output 2 --max-lines 9999 --terminal --wrapper taskset --wrapper-code 124=timeout --wrapper-limit-memory --keep-going --seed 7 --replay 42"
+            )
+            .unwrap(),
+            CliArgs {
+                project_file: "tests.trps".into(),
+                program_path: Some("./prog".into()),
+                program_args: ["-v", "2"]
+                    .map(|text| ArgData {
+                        content_type: ContentType::PlainText,
+                        text: text.to_owned(),
+                    })
+                    .to_vec(),
+                env: vec![
+                    ("LANG".to_owned(), "C".to_owned()),
+                    ("EMPTY".to_owned(), String::new()),
+                ],
+                clear_env: true,
+                working_dir: Some("/tmp".into()),
+                iterations: Some(500),
+                workers: Some(4),
+                time_budget: Some(Duration::from_secs(600)),
+                read_timeout: Some(Duration::from_millis(1500)),
+                run_timeout: Some(Duration::from_millis(9000)),
+                max_run_duration: Some(Duration::from_millis(250)),
+                max_peak_memory: Some(64 << 20),
+                max_cpu_time: Some(Duration::from_millis(100)),
+                max_line_len: Some(4096),
+                max_output_len: Some(2 << 20),
+                max_output_lines: Some(9999),
+                terminal: true,
+                wrapper_command: Some(vec!["taskset".to_owned()]),
+                wrapper_codes: vec![(124, "timeout".to_owned())],
+                wrapper_limit_memory: true,
+                keep_going: true,
+                master_seed: Some(7),
+                replay_seed: Some(42),
+            }
+        );
+    }
+
+    #[test]
+    fn wrapper() {
+        let args: Vec<String> = [
+            "--wrapper-code",
+            "99=Ошибка памяти (valgrind)",
+            "tests.trps",
+            "--wrapper",
+            " valgrind  --error-exitcode=99 ",
+            "--wrapper-code",
+            "124 = timeout",
+        ]
+        .map(str::to_owned)
+        .to_vec();
+
+        assert_eq!(
+            CliArgs::parse(&args).unwrap().wrapper(None).unwrap(),
+            Some(Wrapper {
+                command: vec!["valgrind".to_owned(), "--error-exitcode=99".to_owned()],
+                exit_codes: vec![
+                    (99, "Ошибка памяти (valgrind)".to_owned()),
+                    (124, "timeout".to_owned()),
+                ],
+                limit_address_space: false,
+            })
+        );
+    }
+
+    #[test]
+    fn project_wrapper() {
+        let project = || {
+            Some(Wrapper {
+                command: vec!["taskset".to_owned(), "-c".to_owned(), "0".to_owned()],
+                exit_codes: vec![(1, "taskset".to_owned()), (2, "usage".to_owned())],
+                limit_address_space: false,
+            })
+        };
+
+        let args = parse("a.trps --wrapper-code 2=misuse --wrapper-limit-memory").unwrap();
+        assert_eq!(
+            args.wrapper(project()).unwrap(),
+            Some(Wrapper {
+                command: vec!["taskset".to_owned(), "-c".to_owned(), "0".to_owned()],
+                exit_codes: vec![(1, "taskset".to_owned()), (2, "misuse".to_owned())],
+                limit_address_space: true,
+            })
+        );
+
+        // a new command keeps the codes of the project
+        let args = parse("a.trps --wrapper nice").unwrap();
+        assert_eq!(
+            args.wrapper(project()).unwrap(),
+            Some(Wrapper {
+                command: vec!["nice".to_owned()],
+                ..project().unwrap()
+            })
+        );
+
+        for args in [
+            "a.trps --wrapper-code 99=memory",
+            "a.trps --wrapper-limit-memory",
+        ] {
+            assert!(parse(args).unwrap().wrapper(None).is_err(), "{args}");
+        }
+        assert_eq!(parse("a.trps").unwrap().wrapper(None).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid() {
+        for args in [
+            "",
+            "--seed 1",
+            "a.trps b.trps",
+            "a.trps --iterations",
+            "a.trps --iterations 0",
+            "a.trps --workers 0",
+            "a.trps --time 0",
+            "a.trps --time 1.5",
+            "a.trps --max-duration 0",
+            "a.trps --read-timeout 0",
+            "a.trps --run-timeout soon",
+            "a.trps --env LANG",
+            "a.trps --env =C",
+            "a.trps --working-dir",
+            "a.trps --max-memory -1",
+            "a.trps --max-memory 17592186044416",
+            "a.trps --max-output 18446744073709551615",
+            "a.trps --max-line 0",
+            "a.trps --max-output 1k",
+            "a.trps --max-lines 0",
+            "a.trps --wrapper taskset --wrapper-code 99",
+            "a.trps --wrapper taskset --wrapper-code x=memory",
+            "a.trps --wrapper taskset --wrapper-code 99=",
+            "a.trps --seed -1",
+            "a.trps --verbose",
+        ] {
+            assert!(parse(args).is_err(), "{args}");
+        }
+    }
+}
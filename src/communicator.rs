@@ -1,27 +1,62 @@
 use anyhow::{Error, Result};
-use bstr::{BString, ByteSlice};
+use bstr::{BString, ByteSlice, ByteVec};
 use std::{
+    collections::VecDeque,
     fmt::Display,
-    io::{BufRead, BufReader, Write},
-    process::{Child, ChildStdin, ChildStdout, Command},
+    io::{self, ErrorKind, Read, Write},
+    mem,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 #[derive(Clone, Debug)]
 enum Item {
+    Args(Vec<BString>),
     Stdin(BString),
     Stdout(BString),
+    Stderr(BString),
+    InputClosed,
+    Signal(i32),
 }
 
 impl Display for Item {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::Args(args) => {
+                write!(f, "$")?;
+
+                for arg in args.iter() {
+                    if arg.is_empty() || arg.contains(&b' ') {
+                        write!(f, " \"{}\"", arg)?;
+                    } else {
+                        write!(f, " {}", arg)?;
+                    }
+                }
+
+                Ok(())
+            }
             Self::Stdin(inp) => write!(f, "> {}", inp),
             Self::Stdout(out) => write!(f, "< {}", out),
+            Self::Stderr(err) => write!(f, "! {}", err),
+            Self::InputClosed => write!(f, "* ввод закрыт"),
+            Self::Signal(signal) => {
+                write!(
+                    f,
+                    "* отправлен сигнал {}",
+                    crate::exit_status::signal_name(*signal)
+                )
+            }
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct History {
     items: Vec<Item>,
 }
@@ -36,82 +71,1580 @@ impl Display for History {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommSettings {
+    /// How long a single read may wait for the program's output.
+    pub read_timeout: Option<Duration>,
+    /// Wall-clock limit for the whole run, from spawn to exit.
+    pub run_timeout: Option<Duration>,
+    /// A pause in the output after which an unfinished line counts as a prompt.
+    pub idle_timeout: Duration,
+    /// Runs the program with a pseudo-terminal for stdin and stdout, so that
+    /// it flushes its output like for a human user. Only on Linux.
+    pub terminal: bool,
+    /// In bytes, for a single line of either stream.
+    pub max_line_len: Option<u64>,
+    /// In bytes, for both streams over the whole run.
+    pub max_output_len: Option<u64>,
+    /// For both streams over the whole run. Every line is kept twice (for
+    /// reading and in the history) => many short lines take far more memory
+    /// than their text.
+    pub max_output_lines: Option<u64>,
+}
+
+impl CommSettings {
+    pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(5);
+    pub const DEFAULT_RUN_TIMEOUT: Duration = Duration::from_secs(30);
+    pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_millis(200);
+    pub const DEFAULT_MAX_LINE_LEN: u64 = 1 << 20;
+    pub const DEFAULT_MAX_OUTPUT_LEN: u64 = 16 << 20;
+    pub const DEFAULT_MAX_OUTPUT_LINES: u64 = 1 << 20;
+}
+
+impl Default for CommSettings {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            read_timeout: Some(Self::DEFAULT_READ_TIMEOUT),
+            run_timeout: Some(Self::DEFAULT_RUN_TIMEOUT),
+            idle_timeout: Self::DEFAULT_IDLE_TIMEOUT,
+            terminal: false,
+            max_line_len: Some(Self::DEFAULT_MAX_LINE_LEN),
+            max_output_len: Some(Self::DEFAULT_MAX_OUTPUT_LEN),
+            max_output_lines: Some(Self::DEFAULT_MAX_OUTPUT_LINES),
+        }
+    }
+}
+
+/// What the program used over its whole run, along with the children it waited for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Peak resident set size, in bytes.
+    pub peak_memory: u64,
+    pub user_time: Duration,
+    pub system_time: Duration,
+}
+
+impl ResourceUsage {
+    #[inline]
+    pub fn cpu_time(&self) -> Duration {
+        self.user_time + self.system_time
+    }
+}
+
+#[cfg(unix)]
+impl From<&libc::rusage> for ResourceUsage {
+    fn from(usage: &libc::rusage) -> Self {
+        let duration = |time: libc::timeval| {
+            Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+        };
+
+        // kilobytes everywhere but on macOS
+        #[cfg(target_os = "macos")]
+        let peak_memory = usage.ru_maxrss as u64;
+        #[cfg(not(target_os = "macos"))]
+        let peak_memory = usage.ru_maxrss as u64 * 1024;
+
+        Self {
+            peak_memory,
+            user_time: duration(usage.ru_utime),
+            system_time: duration(usage.ru_stime),
+        }
+    }
+}
+
+impl Display for ResourceUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "пиковая память: {}, время процессора: {} (пользователь), {} (система)",
+            crate::stats::format_bytes(self.peak_memory),
+            crate::stats::format_duration(self.user_time),
+            crate::stats::format_duration(self.system_time)
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeoutKind {
+    Read,
+    Run,
+}
+
+impl Display for TimeoutKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read => write!(f, "Превышено время ожидания вывода программы"),
+            Self::Run => write!(f, "Превышено общее время выполнения программы"),
+        }
+    }
+}
+
+/// The program printed more than it's allowed to and was killed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputLimit {
+    Line,
+    Run,
+    Lines,
+}
+
+impl Display for OutputLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Line => write!(f, "Превышен лимит длины строки вывода"),
+            Self::Run => write!(f, "Превышен лимит объёма вывода программы"),
+            Self::Lines => write!(f, "Превышен лимит количества строк вывода программы"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug)]
+pub enum ReadResult {
+    Line(BString),
+    /// The program closed its output (or exited) before a full line was read.
+    Eof,
+    TimedOut(TimeoutKind),
+    OutputLimit(OutputLimit),
+}
+
+enum Event {
+    Data(Stream, Vec<u8>),
+    Closed(Stream),
+}
+
+#[derive(Debug, Default)]
+struct Pipe {
+    partial: Vec<u8>,
+    lines: VecDeque<BString>,
+    closed: bool,
+}
+
+/// Stops a run from another thread.
+///
+/// On Unix the programs (with everything they spawned) are killed right away,
+/// elsewhere the communicators notice the flag while waiting for output.
+///
+/// Shared by all the runs of a testing session.
+#[derive(Debug, Default)]
+pub struct CancelToken {
+    cancelled: AtomicBool,
+    running: Mutex<Vec<u32>>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        let mut running = self.running.lock().unwrap();
+
+        self.cancelled.store(true, Ordering::Release);
+
+        for pid in running.drain(..) {
+            kill_process_group(pid);
+        }
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Returns `false` if the run was cancelled before the program started.
+    fn attach(&self, pid: u32) -> bool {
+        let mut running = self.running.lock().unwrap();
+
+        if self.is_cancelled() {
+            false
+        } else {
+            running.push(pid);
+            true
+        }
+    }
+
+    /// Must happen before the program is reaped, so that its pid can't be reused.
+    fn detach(&self, pid: u32) {
+        self.running.lock().unwrap().retain(|&other| other != pid);
+    }
+}
+
 pub struct Communicator {
     process: Child,
-    reader: BufReader<ChildStdout>,
-    writer: ChildStdin,
+    cancel: Arc<CancelToken>,
+    events: Receiver<Event>,
+    stdout: Pipe,
+    stderr: Pipe,
+    stderr_log: Vec<u8>,
+    /// Input waiting to be written by the writer thread.
+    writer: Option<Sender<Vec<u8>>>,
+    settings: CommSettings,
+    start_time: Instant,
+    run_deadline: Option<Instant>,
+    timed_out: Option<TimeoutKind>,
+    /// Both streams together.
+    output_len: u64,
+    output_lines: u64,
+    output_limit: Option<OutputLimit>,
+    /// Set once the program is reaped.
+    status: Option<ExitStatus>,
+    duration: Option<Duration>,
+    usage: Option<ResourceUsage>,
     pub history: History,
 }
 
 impl Communicator {
-    #[inline]
-    pub fn new(command: &mut Command) -> Result<Self> {
-        let mut process = command.spawn()?;
+    pub fn new(
+        command: &mut Command,
+        settings: CommSettings,
+        cancel: Arc<CancelToken>,
+    ) -> Result<Self> {
+        #[cfg(target_os = "linux")]
+        let terminal = match settings.terminal {
+            true => Some(crate::terminal::open()?),
+            false => None,
+        };
+        // never `Some` here => the type only has to fit the code below
+        #[cfg(not(target_os = "linux"))]
+        let terminal: Option<(std::fs::File, std::fs::File)> = match settings.terminal {
+            true => anyhow::bail!("Режим терминала доступен только в Linux"),
+            false => None,
+        };
 
-        Ok(Self {
-            reader: BufReader::new(
-                process
-                    .stdout
-                    .take()
-                    .ok_or(Error::msg("program stdout unavailable"))?,
-            ),
-            writer: process
-                .stdin
+        match terminal.as_ref() {
+            Some((_, slave)) => command
+                .stdin(Stdio::from(slave.try_clone()?))
+                .stdout(Stdio::from(slave.try_clone()?)),
+            None => command.stdin(Stdio::piped()).stdout(Stdio::piped()),
+        };
+        command.stderr(Stdio::piped());
+
+        // lets us kill whatever the program spawns along with it
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(command, 0);
+
+        let args: Vec<BString> = command
+            .get_args()
+            .map(|arg| BString::from(Vec::from_os_str_lossy(arg).into_owned()))
+            .collect();
+
+        let mut history = History::default();
+        if !args.is_empty() {
+            history.items.push(Item::Args(args));
+        }
+
+        let spawned = command.spawn();
+
+        // the command keeps its copies of the terminal open => the output
+        // would never close
+        if terminal.is_some() {
+            command.stdin(Stdio::null()).stdout(Stdio::null());
+        }
+
+        let mut process = spawned?;
+        let start_time = Instant::now();
+        let run_deadline = settings.run_timeout.map(|timeout| start_time + timeout);
+
+        if !cancel.attach(process.id()) {
+            kill_process_group(process.id());
+        }
+
+        let (writer, lines) = mpsc::channel();
+        let (sender, events) = mpsc::channel();
+
+        match terminal {
+            Some((master, _)) => {
+                spawn_reader(Stream::Stdout, master.try_clone()?, sender.clone());
+                spawn_writer(master, lines, Some(TERMINAL_EOF));
+            }
+            None => {
+                spawn_reader(
+                    Stream::Stdout,
+                    process
+                        .stdout
+                        .take()
+                        .ok_or(Error::msg("program stdout unavailable"))?,
+                    sender.clone(),
+                );
+                spawn_writer(
+                    process
+                        .stdin
+                        .take()
+                        .ok_or(Error::msg("program stdin unavailable"))?,
+                    lines,
+                    None,
+                );
+            }
+        }
+        spawn_reader(
+            Stream::Stderr,
+            process
+                .stderr
                 .take()
-                .ok_or(Error::msg("program stdin unavailable"))?,
+                .ok_or(Error::msg("program stderr unavailable"))?,
+            sender,
+        );
+
+        Ok(Self {
+            events,
+            stdout: Pipe::default(),
+            stderr: Pipe::default(),
+            stderr_log: Vec::new(),
+            writer: Some(writer),
             process,
-            history: History { items: Vec::new() },
+            cancel,
+            settings,
+            start_time,
+            run_deadline,
+            timed_out: None,
+            output_len: 0,
+            output_lines: 0,
+            output_limit: None,
+            status: None,
+            duration: None,
+            usage: None,
+            history,
         })
     }
 
-    pub fn read_line(&mut self) -> Result<BString> {
-        let mut buffer = Vec::new();
-        self.reader.read_until(b'\n', &mut buffer)?;
+    pub fn read_line(&mut self, stream: Stream) -> Result<ReadResult> {
+        let read_deadline = self.read_deadline();
+
+        loop {
+            if let Some(limit) = self.check_output_limit() {
+                return Ok(ReadResult::OutputLimit(limit));
+            }
+
+            let pipe = self.pipe(stream);
+
+            if let Some(line) = pipe.lines.pop_front() {
+                return Ok(ReadResult::Line(line));
+            } else if pipe.closed {
+                return Ok(ReadResult::Eof);
+            }
+
+            if let Err(kind) = self.receive(read_deadline) {
+                self.time_out(kind);
+
+                return Ok(ReadResult::TimedOut(kind));
+            }
+        }
+    }
+
+    /// Reads a prompt: output that may lack a trailing newline.
+    ///
+    /// The unfinished line is taken as soon as `matches` accepts it, or when
+    /// the program stops printing for `idle_timeout`. Complete lines are
+    /// returned as usual.
+    pub fn read_prompt(
+        &mut self,
+        stream: Stream,
+        matches: impl Fn(&BString) -> bool,
+    ) -> Result<ReadResult> {
+        let read_deadline = self.read_deadline();
+
+        loop {
+            if let Some(limit) = self.check_output_limit() {
+                return Ok(ReadResult::OutputLimit(limit));
+            }
+
+            let pipe = self.pipe(stream);
+
+            if let Some(line) = pipe.lines.pop_front() {
+                return Ok(ReadResult::Line(line));
+            }
+
+            let partial = BString::from(pipe.partial.as_slice());
+
+            if !partial.is_empty() && matches(&partial) {
+                return Ok(ReadResult::Line(self.take_partial(stream)));
+            } else if pipe.closed {
+                return Ok(ReadResult::Eof);
+            }
+
+            let idle_deadline =
+                (!partial.is_empty()).then(|| Instant::now() + self.settings.idle_timeout);
+
+            let limit = self.nearest_deadline(read_deadline);
 
-        let string = BString::from(buffer.as_bstr().trim_end_with(|b| b == '\n'));
-        self.history.items.push(Item::Stdout(string.clone()));
+            match (idle_deadline, limit) {
+                (Some(idle), _) if limit.is_none_or(|(deadline, _)| idle < deadline) => {
+                    if !self.receive_before(Some(idle)) {
+                        // the program is waiting => the prompt is complete
+                        return Ok(ReadResult::Line(self.take_partial(stream)));
+                    }
+                }
+                (_, Some((deadline, kind))) => {
+                    if !self.receive_before(Some(deadline)) {
+                        self.time_out(kind);
 
-        Ok(string)
+                        return Ok(ReadResult::TimedOut(kind));
+                    }
+                }
+                (_, None) => {
+                    self.receive_before(None);
+                }
+            }
+        }
+    }
+
+    /// The longest input line the program can read whole, `None` if any.
+    #[inline]
+    pub fn max_input_line_len(&self) -> Option<usize> {
+        self.settings.terminal.then_some(TERMINAL_MAX_LINE_LEN)
+    }
+
+    /// The byte that would end the input line early, `None` if any byte passes.
+    #[inline]
+    pub fn input_line_end(&self) -> Option<u8> {
+        self.settings.terminal.then_some(TERMINAL_EOF)
     }
 
+    /// Queues a line for the program without waiting for it to be read.
     pub fn write_line(&mut self, mut line: BString) -> Result<()> {
+        // output that arrived before this input should precede it in the history
+        self.poll();
+
         line.push(b'\n');
-        self.writer.write_all(&line)?;
+
+        // fails once the program has closed its stdin => it would never read
+        // the line anyway, and the following steps will tell what happened
+        let _ = self
+            .writer
+            .as_ref()
+            .ok_or(Error::msg("program stdin closed"))?
+            .send(line.to_vec());
 
         self.history.items.push(Item::Stdin(line));
 
         Ok(())
     }
 
+    /// Lets the program see the end of its input once it has read the rest.
+    pub fn close_input(&mut self) {
+        self.poll();
+
+        // the writer closes stdin after the queued input
+        drop(self.writer.take());
+
+        self.history.items.push(Item::InputClosed);
+    }
+
+    /// Sends a signal to the program and whatever it has spawned, as the
+    /// terminal does on Ctrl+C. A program that has already exited is left
+    /// alone.
+    #[cfg(unix)]
+    pub fn send_signal(&mut self, signal: i32) -> Result<()> {
+        self.poll();
+        self.history.items.push(Item::Signal(signal));
+
+        // a reaped program's pid may belong to someone else by now,
+        // while a zombie keeps it
+        if self.status.is_none() {
+            // the program leads its own process group (see `Communicator::new`)
+            unsafe {
+                libc::kill(-(self.process.id() as libc::pid_t), signal);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn send_signal(&mut self, _signal: i32) -> Result<()> {
+        anyhow::bail!("Отправка сигналов доступна только в Unix")
+    }
+
     pub fn finish(mut self) -> Result<CommReport> {
-        let output = self.process.wait_with_output()?;
-
-        let stdout_empty;
-        if !output.stdout.is_empty() {
-            stdout_empty = false;
-            self.history
-                .items
-                .push(Item::Stdout(BString::new(output.stdout)));
-        } else {
-            stdout_empty = true;
+        // the writer closes stdin after the queued input => the program sees EOF
+        drop(self.writer.take());
+
+        let read_deadline = self.read_deadline();
+
+        while !(self.stdout.closed && self.stderr.closed) {
+            if let Some(limit) = self.check_output_limit() {
+                return Ok(CommReport::OutputLimit(mem::take(&mut self.history), limit));
+            }
+
+            if let Err(kind) = self.receive(read_deadline) {
+                self.time_out(kind);
+
+                return Ok(CommReport::TimedOut(mem::take(&mut self.history), kind));
+            }
+        }
+
+        if let Some(limit) = self.check_output_limit() {
+            return Ok(CommReport::OutputLimit(mem::take(&mut self.history), limit));
+        }
+
+        let (status, duration) = match self.wait(read_deadline)? {
+            Ok(status) => (status, self.duration.unwrap_or_default()),
+            Err(kind) => {
+                self.time_out(kind);
+
+                return Ok(CommReport::TimedOut(mem::take(&mut self.history), kind));
+            }
+        };
+
+        Ok(CommReport::Exited {
+            // unread lines are already in the history
+            history: mem::take(&mut self.history),
+            status,
+            duration,
+            usage: self.usage,
+            unread_stdout: !self.stdout.lines.is_empty(),
+            stderr: BString::new(mem::take(&mut self.stderr_log)),
+        })
+    }
+
+    /// Set once a read or the whole run has taken too long.
+    #[inline]
+    pub fn timed_out(&self) -> Option<TimeoutKind> {
+        self.timed_out
+    }
+
+    /// Set once the program has printed too much.
+    #[inline]
+    pub fn output_limit(&self) -> Option<OutputLimit> {
+        self.output_limit
+    }
+
+    /// Everything the program has printed to stderr so far.
+    #[inline]
+    pub fn stderr(&self) -> &[u8] {
+        &self.stderr_log
+    }
+
+    /// Known once the program has been reaped, and only on Unix.
+    #[inline]
+    pub fn usage(&self) -> Option<ResourceUsage> {
+        self.usage
+    }
+
+    /// The exit status of a program that has already finished.
+    ///
+    /// A program that has closed its stdout is given a moment to exit.
+    /// The program is not waited for otherwise.
+    pub fn exit_status(&mut self) -> Option<ExitStatus> {
+        // must happen before the program is reaped
+        self.cancel.detach(self.process.id());
+
+        self.poll();
+
+        let deadline = self
+            .stdout
+            .closed
+            .then(|| Instant::now() + EXIT_GRACE_PERIOD);
+
+        loop {
+            match self.try_reap(false) {
+                Ok(Some(status)) => return Some(status),
+                Ok(None) if deadline.is_some_and(|deadline| Instant::now() < deadline) => {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    #[inline]
+    fn pipe(&mut self, stream: Stream) -> &mut Pipe {
+        match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        }
+    }
+
+    #[inline]
+    fn read_deadline(&self) -> Option<Instant> {
+        self.settings
+            .read_timeout
+            .map(|timeout| Instant::now() + timeout)
+    }
+
+    fn nearest_deadline(&self, read_deadline: Option<Instant>) -> Option<(Instant, TimeoutKind)> {
+        match (read_deadline, self.run_deadline) {
+            (Some(read), Some(run)) if run <= read => Some((run, TimeoutKind::Run)),
+            (Some(read), _) => Some((read, TimeoutKind::Read)),
+            (None, Some(run)) => Some((run, TimeoutKind::Run)),
+            (None, None) => None,
+        }
+    }
+
+    /// Waits for the next piece of output from either stream.
+    fn receive(&mut self, read_deadline: Option<Instant>) -> Result<(), TimeoutKind> {
+        match self.nearest_deadline(read_deadline) {
+            Some((deadline, kind)) if !self.receive_before(Some(deadline)) => Err(kind),
+            Some(_) => Ok(()),
+            None => {
+                self.receive_before(None);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns `false` if nothing arrived before the deadline.
+    fn receive_before(&mut self, deadline: Option<Instant>) -> bool {
+        let event = loop {
+            // wake up regularly to notice cancellation
+            let timeout = match deadline {
+                Some(deadline) => deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(CANCEL_CHECK_INTERVAL),
+                None => CANCEL_CHECK_INTERVAL,
+            };
+
+            match self.events.recv_timeout(timeout) {
+                Ok(event) => break Some(event),
+                Err(RecvTimeoutError::Disconnected) => break None,
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.cancel.is_cancelled() {
+                        // the output closes once the program is dead
+                        self.kill();
+                    }
+
+                    // what the program left running may keep the output open,
+                    // reaping kills it
+                    let _ = self.try_reap(false);
+
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        return false;
+                    }
+                }
+            }
+        };
+
+        match event {
+            Some(event) => self.accept(event),
+            None => {
+                // both readers are gone
+                self.accept(Event::Closed(Stream::Stdout));
+                self.accept(Event::Closed(Stream::Stderr));
+            }
+        }
+
+        true
+    }
+
+    /// Takes in the output that has already arrived, without blocking.
+    fn poll(&mut self) {
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => self.accept(event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.accept(Event::Closed(Stream::Stdout));
+                    self.accept(Event::Closed(Stream::Stderr));
+                    break;
+                }
+            }
+        }
+    }
+
+    fn accept(&mut self, event: Event) {
+        match event {
+            // the program is about to be killed => the rest of its output isn't kept
+            Event::Data(..) if self.output_limit.is_some() => {}
+            Event::Data(stream, data) => {
+                self.output_len += data.len() as u64;
+
+                if self
+                    .settings
+                    .max_output_len
+                    .is_some_and(|max| self.output_len > max)
+                {
+                    self.output_limit = Some(OutputLimit::Run);
+                    return;
+                }
+
+                if stream == Stream::Stderr {
+                    self.stderr_log.extend_from_slice(&data);
+                }
+
+                self.pipe(stream).partial.extend_from_slice(&data);
+
+                while let Some(pos) = self.pipe(stream).partial.find_byte(b'\n') {
+                    let line: Vec<u8> = self.pipe(stream).partial.drain(..=pos).collect();
+                    let mut string = BString::from(line.as_bstr().trim_end_with(|b| b == '\n'));
+
+                    self.output_lines += 1;
+
+                    if self
+                        .settings
+                        .max_output_lines
+                        .is_some_and(|max| self.output_lines > max)
+                    {
+                        self.output_limit = Some(OutputLimit::Lines);
+                        self.pipe(stream).partial.clear();
+                        return;
+                    }
+
+                    self.limit_line(&mut string);
+                    self.push_line(stream, string);
+                }
+
+                let mut partial = mem::take(&mut self.pipe(stream).partial);
+                self.limit_line(&mut partial);
+                self.pipe(stream).partial = partial;
+            }
+            Event::Closed(stream) => {
+                let pipe = self.pipe(stream);
+
+                if pipe.closed {
+                    return;
+                }
+                pipe.closed = true;
+
+                // the last line may lack a trailing newline
+                if !pipe.partial.is_empty() {
+                    let string = BString::new(mem::take(&mut pipe.partial));
+
+                    self.push_line(stream, string);
+                }
+            }
+        }
+    }
+
+    /// Cuts a line that is too long, so that it doesn't take up memory.
+    fn limit_line(&mut self, line: &mut Vec<u8>) {
+        if let Some(max) = self.settings.max_line_len {
+            if line.len() as u64 > max {
+                line.truncate(max as usize);
+                self.output_limit = Some(OutputLimit::Line);
+            }
+        }
+    }
+
+    /// Kills the program once its output has gone over a limit.
+    fn check_output_limit(&mut self) -> Option<OutputLimit> {
+        let limit = self.output_limit?;
+        self.abort();
+
+        Some(limit)
+    }
+
+    fn push_line(&mut self, stream: Stream, line: BString) {
+        self.history.items.push(match stream {
+            Stream::Stdout => Item::Stdout(line.clone()),
+            Stream::Stderr => Item::Stderr(line.clone()),
+        });
+
+        self.pipe(stream).lines.push_back(line);
+    }
+
+    fn take_partial(&mut self, stream: Stream) -> BString {
+        let partial = BString::new(mem::take(&mut self.pipe(stream).partial));
+
+        self.history.items.push(match stream {
+            Stream::Stdout => Item::Stdout(partial.clone()),
+            Stream::Stderr => Item::Stderr(partial.clone()),
+        });
+
+        partial
+    }
+
+    fn wait(&mut self, read_deadline: Option<Instant>) -> Result<Result<ExitStatus, TimeoutKind>> {
+        let limit = self.nearest_deadline(read_deadline);
+
+        self.cancel.detach(self.process.id());
+
+        // short at first => the run duration is measured more precisely
+        let mut next_pause = Duration::from_micros(100);
+
+        loop {
+            if self.cancel.is_cancelled() {
+                self.kill();
+            }
+
+            if let Some(status) = self.try_reap(false)? {
+                return Ok(Ok(status));
+            }
+
+            let now = Instant::now();
+            let mut pause = next_pause;
+            next_pause = (next_pause * 2).min(Duration::from_millis(5));
+
+            if let Some((deadline, kind)) = limit {
+                if now >= deadline {
+                    return Ok(Err(kind));
+                }
+
+                pause = pause.min(deadline - now);
+            }
+
+            thread::sleep(pause);
         }
+    }
+
+    #[inline]
+    fn time_out(&mut self, kind: TimeoutKind) {
+        self.timed_out = Some(kind);
+        self.abort();
+    }
 
-        if output.status.success() {
-            if stdout_empty {
-                Ok(CommReport::Success(self.history))
-            } else {
-                Ok(CommReport::NonEmptyStdout(self.history))
+    /// Kills the program and keeps the unfinished lines in the history.
+    fn abort(&mut self) {
+        self.kill();
+        self.poll();
+
+        for stream in [Stream::Stdout, Stream::Stderr] {
+            if !self.pipe(stream).partial.is_empty() {
+                self.take_partial(stream);
             }
+        }
+    }
+
+    fn kill(&mut self) {
+        self.cancel.detach(self.process.id());
+
+        if self.status.is_none() {
+            // the leader still holds the group id even as a zombie
+            kill_process_group(self.process.id());
+
+            #[cfg(not(unix))]
+            let _ = self.process.kill();
+        }
+
+        let _ = self.try_reap(true);
+    }
+
+    /// Reaps the program if it has exited, or waits for it if `block` is set.
+    ///
+    /// Whatever the program has left running in its process group is killed
+    /// first, while the group id can't be taken by anyone else.
+    #[cfg(unix)]
+    fn try_reap(&mut self, block: bool) -> io::Result<Option<ExitStatus>> {
+        use std::os::unix::process::ExitStatusExt;
+
+        if self.status.is_some() {
+            return Ok(self.status);
+        }
+
+        let pid = self.process.id();
+        let flags = libc::WEXITED | libc::WNOWAIT | if block { 0 } else { libc::WNOHANG };
+        // plain data => zeroes are a valid value
+        let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+
+        // only looks at the exit, the zombie stays
+        loop {
+            let result = unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) };
+
+            if result != -1 {
+                break;
+            }
+            if io::Error::last_os_error().kind() != ErrorKind::Interrupted {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        // nothing has exited yet
+        if unsafe { info.si_pid() } == 0 {
+            return Ok(None);
+        }
+
+        let duration = self.start_time.elapsed();
+        kill_process_group(pid);
+
+        let mut status = 0;
+        // plain data => zeroes are a valid value
+        let mut usage: libc::rusage = unsafe { mem::zeroed() };
+
+        // `Child::try_wait` throws the resource usage away => reap it by hand
+        let result = loop {
+            let result = unsafe { libc::wait4(pid as libc::pid_t, &mut status, 0, &mut usage) };
+
+            if result != -1 || io::Error::last_os_error().kind() != ErrorKind::Interrupted {
+                break result;
+            }
+        };
+
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        self.status = Some(ExitStatus::from_raw(status));
+        self.duration = Some(duration);
+        self.usage = Some(ResourceUsage::from(&usage));
+
+        Ok(self.status)
+    }
+
+    #[cfg(not(unix))]
+    fn try_reap(&mut self, block: bool) -> io::Result<Option<ExitStatus>> {
+        let status = if block {
+            self.process.wait().map(Some)
         } else {
-            let stderr = BString::new(output.stderr);
-            Ok(CommReport::ProgramError(self.history, stderr))
+            self.process.try_wait()
+        }?;
+
+        if status.is_some() && self.duration.is_none() {
+            self.duration = Some(self.start_time.elapsed());
         }
+
+        Ok(status)
     }
 }
 
+impl Drop for Communicator {
+    #[inline]
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Ends the input in the canonical mode of a terminal (`VEOF`, Ctrl+D).
+const TERMINAL_EOF: u8 = 0x04;
+/// The canonical mode cuts longer lines (the buffer holds 4096 bytes with `\n`).
+const TERMINAL_MAX_LINE_LEN: usize = 4095;
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+/// The output closes a little before the program can be reaped.
+const EXIT_GRACE_PERIOD: Duration = Duration::from_millis(100);
+
+#[cfg(unix)]
+fn kill_process_group(pid: u32) {
+    // the program leads its own process group (see `Communicator::new`)
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_pid: u32) {
+    // no handle to the process here => `Communicator` kills it when it notices
+}
+
+/// Writing in a separate thread keeps the program from blocking us when it
+/// doesn't read its input, and lets the timeouts run meanwhile.
+///
+/// A terminal stays open after the input ends => `eof` is written instead.
+fn spawn_writer(mut sink: impl Write + Send + 'static, lines: Receiver<Vec<u8>>, eof: Option<u8>) {
+    thread::spawn(move || {
+        for line in lines {
+            if sink.write_all(&line).is_err() {
+                // the program closed its stdin or died
+                return;
+            }
+        }
+
+        if let Some(eof) = eof {
+            let _ = sink.write_all(&[eof]);
+        }
+    });
+}
+
+fn spawn_reader(stream: Stream, mut source: impl Read + Send + 'static, sender: Sender<Event>) {
+    thread::spawn(move || {
+        let mut buffer = [0; 8192];
+
+        loop {
+            match source.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(len) => {
+                    if sender
+                        .send(Event::Data(stream, buffer[..len].to_vec()))
+                        .is_err()
+                    {
+                        // communicator dropped => nobody needs the output
+                        return;
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+
+        let _ = sender.send(Event::Closed(stream));
+    });
+}
+
 #[derive(Debug)]
 pub enum CommReport {
-    Success(History),
-    NonEmptyStdout(History),
-    ProgramError(History, BString),
+    Exited {
+        history: History,
+        status: ExitStatus,
+        /// From spawn to exit.
+        duration: Duration,
+        /// `None` where it can't be measured.
+        usage: Option<ResourceUsage>,
+        unread_stdout: bool,
+        stderr: BString,
+    },
+    TimedOut(History, TimeoutKind),
+    OutputLimit(History, OutputLimit),
+}
+
+//===================================================================================//
+//===================================// TESTING //===================================//
+//===================================================================================//
+
+#[cfg(all(test, unix))]
+mod test_util {
+    use super::{CancelToken, CommSettings, Communicator};
+    use std::{process::Command, sync::Arc};
+
+    /// Runs `script` with `sh -c`.
+    pub(super) fn shell(script: &str, settings: CommSettings) -> Communicator {
+        shell_with_cancel(script, settings, Default::default())
+    }
+
+    pub(super) fn shell_with_cancel(
+        script: &str,
+        settings: CommSettings,
+        cancel: Arc<CancelToken>,
+    ) -> Communicator {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+
+        Communicator::new(&mut command, settings, cancel).unwrap()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_timeouts {
+    use super::{test_util::shell, CommReport, CommSettings, ReadResult, Stream, TimeoutKind};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn read_timeout() {
+        let settings = CommSettings {
+            read_timeout: Some(Duration::from_millis(100)),
+            run_timeout: None,
+            ..Default::default()
+        };
+        let mut comm = shell("printf 'Enter N: '; sleep 10", settings);
+
+        let start = Instant::now();
+        let result = comm.read_line(Stream::Stdout).unwrap();
+
+        assert!(matches!(result, ReadResult::TimedOut(TimeoutKind::Read)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        // the first line holds the shell's arguments
+        assert!(comm.history.to_string().ends_with("\n< Enter N: \n"));
+    }
+
+    #[test]
+    fn run_timeout_on_finish() {
+        let settings = CommSettings {
+            read_timeout: None,
+            run_timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let mut comm = shell("echo hi; sleep 10", settings);
+
+        assert!(
+            matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(line) if line == "hi")
+        );
+        assert!(matches!(
+            comm.finish().unwrap(),
+            CommReport::TimedOut(_, TimeoutKind::Run)
+        ));
+    }
+
+    #[test]
+    fn no_timeout() {
+        let mut comm = shell("read x; echo \"got $x\"", CommSettings::default());
+
+        comm.write_line("42".into()).unwrap();
+
+        assert!(
+            matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(line) if line == "got 42")
+        );
+        assert!(matches!(
+            comm.finish().unwrap(),
+            CommReport::Exited { status, unread_stdout: false, .. } if status.success()
+        ));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_eof {
+    use super::{test_util::shell, CommReport, CommSettings, ReadResult, Stream};
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn empty_line_is_not_eof() {
+        let mut comm = shell("echo", CommSettings::default());
+
+        assert!(
+            matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(line) if line.is_empty())
+        );
+        assert!(matches!(
+            comm.read_line(Stream::Stdout).unwrap(),
+            ReadResult::Eof
+        ));
+    }
+
+    #[test]
+    fn last_line_without_newline() {
+        let mut comm = shell("printf 'a\\nb'", CommSettings::default());
+
+        assert!(
+            matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(line) if line == "a")
+        );
+        assert!(
+            matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(line) if line == "b")
+        );
+        assert!(matches!(
+            comm.read_line(Stream::Stdout).unwrap(),
+            ReadResult::Eof
+        ));
+    }
+
+    #[test]
+    fn crashed_program() {
+        let mut comm = shell("exit 3", CommSettings::default());
+
+        assert!(matches!(
+            comm.read_line(Stream::Stdout).unwrap(),
+            ReadResult::Eof
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn background_child() {
+        let settings = CommSettings {
+            read_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let mut comm = shell("sleep 37 & echo $!", settings);
+
+        let pid = match comm.read_line(Stream::Stdout).unwrap() {
+            ReadResult::Line(line) => line.to_string(),
+            result => panic!("{result:?}"),
+        };
+
+        // the child keeps the output open after the program exits
+        match comm.finish().unwrap() {
+            CommReport::Exited { status, .. } => assert!(status.success()),
+            report => panic!("{report:?}"),
+        }
+
+        // gone or at least a zombie waiting for `init`, once it's done dying
+        let dead = || {
+            let state = std::fs::read_to_string(format!("/proc/{pid}/stat")).unwrap_or_default();
+            state.is_empty() || state.contains(") Z ")
+        };
+        let start = Instant::now();
+
+        while !dead() && start.elapsed() < Duration::from_secs(1) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(dead());
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_stderr {
+    use super::{test_util::shell, CommReport, CommSettings, ReadResult, Stream};
+
+    #[test]
+    fn separate_streams() {
+        let mut comm = shell("echo out; echo err >&2", CommSettings::default());
+
+        assert!(
+            matches!(comm.read_line(Stream::Stderr).unwrap(), ReadResult::Line(line) if line == "err")
+        );
+        assert!(
+            matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(line) if line == "out")
+        );
+        assert!(matches!(
+            comm.finish().unwrap(),
+            CommReport::Exited { status, unread_stdout: false, .. } if status.success()
+        ));
+    }
+
+    #[test]
+    fn interleaved_history() {
+        let mut comm = shell(
+            "echo 'Enter N:' >&2; read n; echo $((n * 2))",
+            CommSettings::default(),
+        );
+
+        assert!(
+            matches!(comm.read_line(Stream::Stderr).unwrap(), ReadResult::Line(line) if line == "Enter N:")
+        );
+        comm.write_line("21".into()).unwrap();
+        assert!(
+            matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(line) if line == "42")
+        );
+
+        match comm.finish().unwrap() {
+            CommReport::Exited { history, .. } => {
+                assert!(history
+                    .to_string()
+                    .ends_with("\n! Enter N:\n> 21\n\n< 42\n"))
+            }
+            report => panic!("{report:?}"),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_prompt {
+    use super::{test_util::shell, CommSettings, ReadResult, Stream};
+
+    #[test]
+    fn matching_prompt() {
+        let mut comm = shell(
+            "printf 'Enter N: '; read n; echo $n",
+            CommSettings::default(),
+        );
+
+        let result = comm
+            .read_prompt(Stream::Stdout, |text| text == "Enter N: ")
+            .unwrap();
+        assert!(matches!(result, ReadResult::Line(line) if line == "Enter N: "));
+
+        comm.write_line("5".into()).unwrap();
+
+        let result = comm.read_line(Stream::Stdout).unwrap();
+        assert!(matches!(result, ReadResult::Line(line) if line == "5"));
+    }
+
+    #[test]
+    fn idle_prompt() {
+        let mut comm = shell("printf 'Enter: '; sleep 10", CommSettings::default());
+
+        let result = comm
+            .read_prompt(Stream::Stdout, |text| text == "Name: ")
+            .unwrap();
+        assert!(matches!(result, ReadResult::Line(line) if line == "Enter: "));
+    }
+
+    #[test]
+    fn full_line() {
+        let mut comm = shell("echo 'Hello'; printf 'Enter: '", CommSettings::default());
+
+        let result = comm
+            .read_prompt(Stream::Stdout, |text| text == "Enter: ")
+            .unwrap();
+        assert!(matches!(result, ReadResult::Line(line) if line == "Hello"));
+    }
+
+    #[test]
+    fn stderr_prompt() {
+        let mut comm = shell(
+            "printf 'out: '; printf 'Enter N: ' >&2; read n; echo $n >&2",
+            CommSettings::default(),
+        );
+
+        let result = comm
+            .read_prompt(Stream::Stderr, |text| text == "Enter N: ")
+            .unwrap();
+        assert!(matches!(result, ReadResult::Line(line) if line == "Enter N: "));
+
+        comm.write_line("5".into()).unwrap();
+
+        let result = comm.read_line(Stream::Stderr).unwrap();
+        assert!(matches!(result, ReadResult::Line(line) if line == "5"));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_cancel {
+    use super::{
+        test_util::shell_with_cancel, CancelToken, CommSettings, Communicator, ReadResult, Stream,
+    };
+    use std::{
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn cancel_kills_program() {
+        let cancel = Arc::new(CancelToken::default());
+        let settings = CommSettings {
+            read_timeout: None,
+            run_timeout: None,
+            ..Default::default()
+        };
+
+        let mut comm = shell_with_cancel("sleep 10 & sleep 10", settings, cancel.clone());
+
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            cancel.cancel();
+        });
+
+        let start = Instant::now();
+
+        assert!(matches!(
+            comm.read_line(Stream::Stdout).unwrap(),
+            ReadResult::Eof
+        ));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn cancel_kills_every_program() {
+        let cancel = Arc::new(CancelToken::default());
+        let settings = CommSettings {
+            read_timeout: None,
+            run_timeout: None,
+            ..Default::default()
+        };
+
+        let mut comms: Vec<Communicator> = (0..3)
+            .map(|_| shell_with_cancel("sleep 10", settings, cancel.clone()))
+            .collect();
+
+        let start = Instant::now();
+        cancel.cancel();
+
+        for comm in comms.iter_mut() {
+            assert!(matches!(
+                comm.read_line(Stream::Stdout).unwrap(),
+                ReadResult::Eof
+            ));
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn cancel_before_start() {
+        let cancel = Arc::new(CancelToken::default());
+        cancel.cancel();
+
+        let mut comm = shell_with_cancel("sleep 10", CommSettings::default(), cancel);
+
+        assert!(matches!(
+            comm.read_line(Stream::Stdout).unwrap(),
+            ReadResult::Eof
+        ));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_output_limit {
+    use super::{test_util::shell, CommReport, CommSettings, OutputLimit, ReadResult, Stream};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn endless_output() {
+        let settings = CommSettings {
+            max_output_len: Some(1 << 16),
+            ..Default::default()
+        };
+        let mut comm = shell("echo first; yes", settings);
+
+        assert!(
+            matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(line) if line == "first")
+        );
+
+        let start = Instant::now();
+
+        match comm.finish().unwrap() {
+            CommReport::OutputLimit(history, OutputLimit::Run) => {
+                assert!(history.to_string().len() < 1 << 17)
+            }
+            report => panic!("{report:?}"),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn many_lines() {
+        let settings = CommSettings {
+            max_output_lines: Some(1000),
+            ..Default::default()
+        };
+        let comm = shell("yes", settings);
+
+        match comm.finish().unwrap() {
+            CommReport::OutputLimit(history, OutputLimit::Lines) => {
+                let history = history.to_string();
+                assert_eq!(
+                    history.lines().filter(|line| line.starts_with('<')).count(),
+                    1000
+                );
+            }
+            report => panic!("{report:?}"),
+        }
+    }
+
+    #[test]
+    fn bytes_not_lines() {
+        // 1.9 МиБ in 300000 lines
+        let settings = CommSettings {
+            max_output_len: Some(2 << 20),
+            ..Default::default()
+        };
+        let comm = shell("seq 1 300000", settings);
+
+        assert!(matches!(comm.finish().unwrap(), CommReport::Exited { .. }));
+    }
+
+    #[test]
+    fn long_line() {
+        let settings = CommSettings {
+            max_line_len: Some(1000),
+            ..Default::default()
+        };
+        let mut comm = shell("tr -d '\\n' < /dev/zero | tr '\\0' a; sleep 10", settings);
+
+        assert!(matches!(
+            comm.read_line(Stream::Stdout).unwrap(),
+            ReadResult::OutputLimit(OutputLimit::Line)
+        ));
+        assert_eq!(comm.output_limit(), Some(OutputLimit::Line));
+    }
+
+    #[test]
+    fn within_limits() {
+        let settings = CommSettings {
+            max_line_len: Some(3),
+            max_output_len: Some(8),
+            max_output_lines: Some(2),
+            ..Default::default()
+        };
+        let mut comm = shell("echo abc; echo def", settings);
+
+        for expected in ["abc", "def"] {
+            assert!(
+                matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(line) if line == expected)
+            );
+        }
+        assert_eq!(comm.output_limit(), None);
+        assert!(matches!(comm.finish().unwrap(), CommReport::Exited { .. }));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_input {
+    use super::{test_util::shell, CommReport, CommSettings, ReadResult, Stream, TimeoutKind};
+    use bstr::BString;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn large_line_echoed() {
+        let line = BString::from("x".repeat(512 << 10));
+        let mut comm = shell("cat", CommSettings::default());
+
+        comm.write_line(line.clone()).unwrap();
+
+        assert!(
+            matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(echo) if echo == line)
+        );
+        assert!(matches!(
+            comm.finish().unwrap(),
+            CommReport::Exited { status, .. } if status.success()
+        ));
+    }
+
+    #[test]
+    fn input_never_read() {
+        let settings = CommSettings {
+            read_timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let mut comm = shell("sleep 10", settings);
+
+        let start = Instant::now();
+
+        comm.write_line(BString::from("x".repeat(1 << 20))).unwrap();
+
+        assert!(matches!(
+            comm.read_line(Stream::Stdout).unwrap(),
+            ReadResult::TimedOut(TimeoutKind::Read)
+        ));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn stdin_closed_early() {
+        let mut comm = shell("exec 0<&-; echo done", CommSettings::default());
+
+        comm.write_line("ignored".into()).unwrap();
+        comm.write_line("ignored".into()).unwrap();
+
+        assert!(
+            matches!(comm.read_line(Stream::Stdout).unwrap(), ReadResult::Line(line) if line == "done")
+        );
+        assert!(matches!(comm.finish().unwrap(), CommReport::Exited { .. }));
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test_terminal {
+    use super::{test_util, CommReport, CommSettings, Communicator, ReadResult, Stream};
+
+    fn shell(script: &str) -> Communicator {
+        let settings = CommSettings {
+            terminal: true,
+            ..Default::default()
+        };
+
+        test_util::shell(script, settings)
+    }
+
+    fn read(comm: &mut Communicator, stream: Stream) -> String {
+        match comm.read_line(stream).unwrap() {
+            ReadResult::Line(line) => line.to_string(),
+            result => panic!("{result:?}"),
+        }
+    }
+
+    #[test]
+    fn is_a_terminal() {
+        let mut comm = shell("[ -t 0 ] && [ -t 1 ] && [ ! -t 2 ] && echo yes");
+
+        assert_eq!(read(&mut comm, Stream::Stdout), "yes");
+    }
+
+    #[test]
+    fn no_echo_and_no_carriage_returns() {
+        let mut comm = shell("read x; echo \"got $x\"; echo err >&2");
+
+        comm.write_line("42".into()).unwrap();
+
+        assert_eq!(read(&mut comm, Stream::Stdout), "got 42");
+        assert_eq!(read(&mut comm, Stream::Stderr), "err");
+        assert!(matches!(
+            comm.read_line(Stream::Stdout).unwrap(),
+            ReadResult::Eof
+        ));
+    }
+
+    #[test]
+    fn end_of_input() {
+        let mut comm = shell("cat; echo end");
+
+        comm.write_line("a".into()).unwrap();
+        comm.write_line("\x03\x11b\r".into()).unwrap();
+
+        assert_eq!(read(&mut comm, Stream::Stdout), "a");
+        assert_eq!(read(&mut comm, Stream::Stdout), "\x03\x11b\r");
+
+        match comm.finish().unwrap() {
+            CommReport::Exited {
+                history, status, ..
+            } => {
+                assert!(status.success());
+                assert!(history.to_string().ends_with("< end\n"));
+            }
+            report => panic!("{report:?}"),
+        }
+    }
+    #[test]
+    fn control_bytes_pass() {
+        let mut comm = shell("od -An -tx1");
+
+        // erase and kill keys between the usual bytes
+        comm.write_line("ab\x7fc\x15xy".into()).unwrap();
+        comm.close_input();
+
+        assert_eq!(
+            read(&mut comm, Stream::Stdout).trim(),
+            "61 62 7f 63 15 78 79 0a"
+        );
+    }
 }
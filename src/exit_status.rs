@@ -0,0 +1,282 @@
+use crate::{
+    rule_data::{RuleData, RuleType},
+    rules::{IntRanges, Rule},
+    worker_thread::OpReport,
+};
+use std::process::ExitStatus;
+
+#[derive(Debug)]
+pub enum ExitExpectation {
+    Code(IntRanges),
+    /// `None` accepts any signal.
+    Signal(Option<IntRanges>),
+}
+
+impl ExitExpectation {
+    pub fn parse(rule: &RuleData) -> anyhow::Result<Self> {
+        match rule.rule_type {
+            RuleType::ExitCode => Ok(Self::Code(IntRanges::parse(&rule.text)?)),
+            RuleType::Signal if rule.text.trim().is_empty() => Ok(Self::Signal(None)),
+            RuleType::Signal => Ok(Self::Signal(Some(IntRanges::parse(&rule.text)?))),
+            _ => anyhow::bail!("Правило «{}» не описывает завершение программы", rule.name),
+        }
+    }
+
+    /// Whether the program may be killed by `signal`.
+    pub fn expects_signal(&self, signal: i32) -> bool {
+        match self {
+            Self::Code(_) => false,
+            Self::Signal(None) => true,
+            Self::Signal(Some(signals)) => signals.contains(signal.into()),
+        }
+    }
+
+    pub fn validate(&self, status: ExitStatus) -> OpReport {
+        let matches = match self {
+            Self::Code(codes) => status
+                .code()
+                .is_some_and(|code| codes.contains(code.into())),
+            Self::Signal(_) => signal(status).is_some_and(|signal| self.expects_signal(signal)),
+        };
+
+        if matches {
+            OpReport::Success
+        } else {
+            OpReport::Failure {
+                error_message: format!("{}, получено: {}", self, describe(status)),
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ExitExpectation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Code(codes) => write!(f, "Ожидался код завершения из: {}", codes.as_str()),
+            Self::Signal(None) => write!(f, "Ожидалось завершение программы сигналом"),
+            Self::Signal(Some(signals)) => {
+                write!(f, "Ожидалось завершение сигналом из: {}", signals.as_str())
+            }
+        }
+    }
+}
+
+pub fn describe(status: ExitStatus) -> String {
+    match (status.code(), signal(status)) {
+        (Some(code), _) => format!("код завершения {}", code),
+        (None, Some(signal)) if core_dumped(status) => {
+            format!(
+                "завершена сигналом {}, создан дамп памяти",
+                describe_signal(signal)
+            )
+        }
+        (None, Some(signal)) => format!("завершена сигналом {}", describe_signal(signal)),
+        (None, None) => "неизвестный статус завершения".to_owned(),
+    }
+}
+
+#[cfg(unix)]
+#[inline]
+pub fn signal(status: ExitStatus) -> Option<i32> {
+    std::os::unix::process::ExitStatusExt::signal(&status)
+}
+
+#[cfg(not(unix))]
+#[inline]
+pub fn signal(_status: ExitStatus) -> Option<i32> {
+    None
+}
+
+#[cfg(unix)]
+#[inline]
+fn core_dumped(status: ExitStatus) -> bool {
+    std::os::unix::process::ExitStatusExt::core_dumped(&status)
+}
+
+#[cfg(not(unix))]
+#[inline]
+fn core_dumped(_status: ExitStatus) -> bool {
+    false
+}
+
+/// Reads a signal for the program: `INT`, `SIGTERM`, `hup` or `2`.
+#[cfg(unix)]
+pub fn parse_signal(text: &str) -> anyhow::Result<i32> {
+    let text = text.trim();
+
+    SENDABLE_SIGNALS
+        .into_iter()
+        .find(|&signal| {
+            let name = signal_name(signal);
+
+            name.eq_ignore_ascii_case(text)
+                || name["SIG".len()..].eq_ignore_ascii_case(text)
+                || signal.to_string() == text
+        })
+        .ok_or_else(|| {
+            anyhow::format_err!(
+                "неизвестный сигнал «{}» (допустимы: SIGINT, SIGTERM, SIGHUP)",
+                text
+            )
+        })
+}
+
+#[cfg(not(unix))]
+pub fn parse_signal(_text: &str) -> anyhow::Result<i32> {
+    anyhow::bail!("отправка сигналов доступна только в Unix")
+}
+
+/// `SIGINT` and the like, or just the number for the less known ones.
+pub fn signal_name(signal: i32) -> String {
+    match known_signal(signal) {
+        Some((name, _)) => name.to_owned(),
+        None => signal.to_string(),
+    }
+}
+
+/// `SIGSEGV (11, нарушение доступа к памяти)`.
+pub fn describe_signal(signal: i32) -> String {
+    match known_signal(signal) {
+        Some((name, meaning)) => format!("{} ({}, {})", name, signal, meaning),
+        None => signal.to_string(),
+    }
+}
+
+#[cfg(unix)]
+fn known_signal(signal: i32) -> Option<(&'static str, &'static str)> {
+    SIGNALS
+        .iter()
+        .find(|(number, _, _)| *number == signal)
+        .map(|(_, name, meaning)| (*name, *meaning))
+}
+
+#[cfg(not(unix))]
+fn known_signal(_signal: i32) -> Option<(&'static str, &'static str)> {
+    None
+}
+
+/// The numbers differ between systems => only the names are portable.
+#[cfg(unix)]
+const SIGNALS: [(i32, &str, &str); 16] = [
+    (libc::SIGHUP, "SIGHUP", "обрыв терминала"),
+    (libc::SIGINT, "SIGINT", "прерывание, Ctrl+C"),
+    (libc::SIGQUIT, "SIGQUIT", "выход, Ctrl+\\"),
+    (libc::SIGILL, "SIGILL", "недопустимая инструкция"),
+    (libc::SIGTRAP, "SIGTRAP", "ловушка отладчика"),
+    (libc::SIGABRT, "SIGABRT", "аварийное завершение, abort()"),
+    (libc::SIGBUS, "SIGBUS", "ошибка шины"),
+    (libc::SIGFPE, "SIGFPE", "арифметическая ошибка"),
+    (libc::SIGKILL, "SIGKILL", "принудительное завершение"),
+    (libc::SIGSEGV, "SIGSEGV", "нарушение доступа к памяти"),
+    (libc::SIGPIPE, "SIGPIPE", "запись в закрытый канал"),
+    (libc::SIGALRM, "SIGALRM", "сигнал таймера"),
+    (libc::SIGTERM, "SIGTERM", "запрос завершения"),
+    (
+        libc::SIGXCPU,
+        "SIGXCPU",
+        "превышен лимит процессорного времени",
+    ),
+    (libc::SIGXFSZ, "SIGXFSZ", "превышен лимит размера файла"),
+    (libc::SIGSYS, "SIGSYS", "недопустимый системный вызов"),
+];
+
+/// What a user can do to a program from the terminal.
+#[cfg(unix)]
+const SENDABLE_SIGNALS: [i32; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+//===================================================================================//
+//===================================// TESTING //===================================//
+//===================================================================================//
+
+#[cfg(all(test, unix))]
+mod test_exit_expectation {
+    use super::ExitExpectation;
+    use crate::{
+        rule_data::{RuleData, RuleType},
+        worker_thread::OpReport,
+    };
+    use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
+    fn expectation(rule_type: RuleType, text: &str) -> ExitExpectation {
+        ExitExpectation::parse(&RuleData {
+            rule_type,
+            text: text.to_owned(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[inline]
+    fn exited(code: i32) -> ExitStatus {
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[inline]
+    fn killed(signal: i32) -> ExitStatus {
+        ExitStatus::from_raw(signal)
+    }
+
+    #[test]
+    fn single_code() {
+        let exp = expectation(RuleType::ExitCode, "2");
+
+        assert_eq!(exp.validate(exited(2)), OpReport::Success);
+        assert_ne!(exp.validate(exited(0)), OpReport::Success);
+        assert_ne!(exp.validate(killed(9)), OpReport::Success);
+    }
+
+    #[test]
+    fn code_set_and_range() {
+        let exp = expectation(RuleType::ExitCode, "0, 10..20");
+
+        assert_eq!(exp.validate(exited(0)), OpReport::Success);
+        assert_eq!(exp.validate(exited(15)), OpReport::Success);
+        assert_ne!(exp.validate(exited(2)), OpReport::Success);
+    }
+
+    #[test]
+    fn any_signal() {
+        let exp = expectation(RuleType::Signal, "  ");
+
+        assert_eq!(exp.validate(killed(15)), OpReport::Success);
+        assert_ne!(exp.validate(exited(0)), OpReport::Success);
+    }
+
+    #[test]
+    fn signal_names() {
+        for text in ["INT", "SIGINT", " int ", "2"] {
+            assert_eq!(super::parse_signal(text).unwrap(), libc::SIGINT, "{text}");
+        }
+
+        assert_eq!(super::parse_signal("sigterm").unwrap(), libc::SIGTERM);
+        assert!(super::parse_signal("KILL").is_err());
+        assert!(super::parse_signal("9").is_err());
+        assert!(super::parse_signal("").is_err());
+
+        assert_eq!(super::signal_name(libc::SIGHUP), "SIGHUP");
+    }
+
+    #[test]
+    fn crash_description() {
+        assert_eq!(
+            super::describe(killed(libc::SIGSEGV)),
+            "завершена сигналом SIGSEGV (11, нарушение доступа к памяти)"
+        );
+        // the core dump flag
+        assert!(
+            super::describe(ExitStatus::from_raw(libc::SIGABRT | 0x80)).starts_with(
+                "завершена сигналом SIGABRT (6, аварийное завершение, abort()), создан дамп памяти"
+            )
+        );
+        assert_eq!(super::describe(killed(64)), "завершена сигналом 64");
+        assert_eq!(super::describe(exited(3)), "код завершения 3");
+    }
+
+    #[test]
+    fn chosen_signal() {
+        let exp = expectation(RuleType::Signal, "9, 15");
+
+        assert_eq!(exp.validate(killed(9)), OpReport::Success);
+        assert_ne!(exp.validate(killed(11)), OpReport::Success);
+    }
+}
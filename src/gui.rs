@@ -1,18 +1,23 @@
-use crate::run_manager::{RunManager, TestReport, TestingData};
 use anyhow::Result;
 use eframe::{
     egui::{self, Color32},
     App,
 };
 use file_select::UiFileSelect;
+use final_trps::{
+    project::Project,
+    run_manager::{self, FailureReport, RunManager, Summary, TestReport, TestingData},
+    stats::RunStats,
+};
+use project_file::UiProjectFile;
 use rule_panel::UiRulePanel;
+use settings::UiSettings;
 use std::sync::atomic::Ordering;
 
 mod file_select;
-mod rule_data;
+mod project_file;
 mod rule_panel;
-
-pub use rule_data::{ContentType, RuleData, RuleType};
+mod settings;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum AppState {
@@ -25,10 +30,14 @@ enum AppState {
 pub struct AppGui {
     run_manager: RunManager,
     successes_required: u32,
+    workers: u32,
+    continue_on_failure: bool,
     state: AppState,
 
+    ui_project_file: UiProjectFile,
     ui_file_select: UiFileSelect,
     ui_rule_panel: UiRulePanel,
+    ui_settings: UiSettings,
 }
 
 impl AppGui {
@@ -39,23 +48,68 @@ impl AppGui {
         Ok(Self {
             run_manager: RunManager::create_and_start_thread(),
             successes_required: 1,
+            workers: run_manager::default_workers(),
+            continue_on_failure: false,
             state: AppState::Idle,
 
+            ui_project_file: Default::default(),
             ui_file_select: Default::default(),
             ui_rule_panel: Default::default(),
+            ui_settings: Default::default(),
         })
     }
 
     #[inline]
-    fn collect_testing_data(&self) -> TestingData {
+    fn collect_testing_data(&self, replay_seed: Option<u64>) -> TestingData {
         TestingData {
             program_path: self.ui_file_select.program_file.as_ref().unwrap().clone(),
             rules: self.ui_rule_panel.rules().clone(),
             successes_required: self.successes_required,
+            workers: self.workers,
+            continue_on_failure: self.continue_on_failure,
+            time_budget: self.ui_settings.time_budget,
+            max_run_duration: self.ui_settings.max_run_duration,
+            max_peak_memory: self.ui_settings.max_peak_memory,
+            max_cpu_time: self.ui_settings.max_cpu_time,
+            comm_settings: self.ui_settings.comm,
+            launch: self.ui_settings.launch.clone(),
+            master_seed: self.ui_settings.master_seed,
+            replay_seed,
+        }
+    }
+
+    fn open_project(&mut self, project: Project) {
+        if project.program_path.is_some() {
+            self.ui_file_select.program_file = project.program_path;
         }
+
+        self.ui_rule_panel.set_rules(project.rules);
+        self.successes_required = project.successes_required;
+        self.ui_settings.max_run_duration = project.max_run_duration;
+        self.ui_settings.max_peak_memory = project.max_peak_memory;
+        self.ui_settings.max_cpu_time = project.max_cpu_time;
+        self.ui_settings
+            .open_project(project.comm_settings, project.launch);
     }
 
     fn ui_main(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        let opened = self.ui_project_file.display(ctx, ui, || Project {
+            program_path: self.ui_file_select.program_file.clone(),
+            rules: self.ui_rule_panel.rules().clone(),
+            successes_required: self.successes_required,
+            max_run_duration: self.ui_settings.max_run_duration,
+            max_peak_memory: self.ui_settings.max_peak_memory,
+            max_cpu_time: self.ui_settings.max_cpu_time,
+            comm_settings: self.ui_settings.comm,
+            launch: self.ui_settings.launch.clone(),
+        });
+
+        if let Some(project) = opened {
+            self.open_project(project);
+        }
+
+        ui.separator();
+
         self.ui_file_select.display(ctx, ui);
 
         if self.ui_file_select.is_file_selected() {
@@ -65,6 +119,10 @@ impl AppGui {
 
             ui.separator();
 
+            self.ui_settings.display(ctx, ui);
+
+            ui.separator();
+
             let slider = egui::Slider::new(&mut self.successes_required, 1..=10_000_000)
                 .text("Требуемое количество успешных тестов")
                 .logarithmic(true)
@@ -72,6 +130,18 @@ impl AppGui {
 
             ui.add(slider);
 
+            let slider = egui::Slider::new(&mut self.workers, 1..=256)
+                .text("Количество одновременных запусков")
+                .logarithmic(true)
+                .integer();
+
+            ui.add(slider);
+
+            ui.checkbox(
+                &mut self.continue_on_failure,
+                "Продолжать тестирование после ошибок",
+            );
+
             match self.state {
                 AppState::Idle if !self.ui_rule_panel.rules().is_empty() => {
                     self.ui_start_button(ui);
@@ -90,14 +160,18 @@ impl AppGui {
     #[inline]
     fn ui_start_button(&mut self, ui: &mut egui::Ui) {
         if ui.button("Начать тестирование").clicked() {
-            let testing_data = self.collect_testing_data();
+            self.start_testing(None);
+        }
+    }
 
-            if !self.run_manager.send_testing_data(testing_data) {
-                return;
-            }
+    fn start_testing(&mut self, replay_seed: Option<u64>) {
+        let testing_data = self.collect_testing_data(replay_seed);
 
-            self.state = AppState::Working;
+        if !self.run_manager.send_testing_data(testing_data) {
+            return;
         }
+
+        self.state = AppState::Working;
     }
 
     fn ui_footer_working(&mut self, ui: &mut egui::Ui) {
@@ -107,25 +181,40 @@ impl AppGui {
             self.state = AppState::Finished;
         }
 
-        let tests_solved = self
-            .run_manager
-            .work_state
-            .solved_tests
-            .load(Ordering::Acquire);
-        let tests_required = self
-            .run_manager
-            .work_state
-            .required_tests
-            .load(Ordering::Acquire);
+        let work_state = &self.run_manager.work_state;
+
+        // the indices handed out run past the target by up to one per worker
+        let tests_solved = work_state.finished_runs();
+        let tests_required = work_state.required_tests.load(Ordering::Acquire);
+        let elapsed = work_state.elapsed();
 
-        let progress = (tests_solved as f32) / (tests_required as f32);
+        let mut progress = (tests_solved as f32) / (tests_required as f32);
 
-        let progress_bar = egui::ProgressBar::new(progress)
+        // whichever runs out first
+        if let Some(budget) = self.ui_settings.time_budget {
+            progress = progress.max(elapsed.as_secs_f32() / budget.as_secs_f32());
+        }
+
+        let progress_bar = egui::ProgressBar::new(progress.min(1.0))
             .show_percentage()
             .text(format!("Прогресс: {}/{}", tests_solved, tests_required));
 
         ui.add(progress_bar);
 
+        ui.label(format!(
+            "Прошло: {} с, запусков в секунду: {:.1}",
+            elapsed.as_secs(),
+            work_state.throughput()
+        ));
+
+        ui.label(format!(
+            "Успешно: {}, ошибки: {}, аварийные завершения: {}, превышения времени: {}",
+            work_state.passed.load(Ordering::Acquire),
+            work_state.failed.load(Ordering::Acquire),
+            work_state.crashed.load(Ordering::Acquire),
+            work_state.timed_out.load(Ordering::Acquire),
+        ));
+
         match self.run_manager.try_receive_result() {
             Some(true) => {
                 self.state = AppState::Finished;
@@ -140,20 +229,52 @@ impl AppGui {
     fn ui_footer_finished(&mut self, ui: &mut egui::Ui) {
         ui.separator();
 
+        let mut replay_seed = None;
+
         match self.run_manager.last_report.as_mut() {
-            Some(TestReport::Success) => {
+            Some(TestReport::Success(run_stats)) => {
                 ui.colored_label(Color32::GREEN, "Все тесты прошли успешно");
+
+                ui_stats(ui, run_stats);
             }
-            Some(TestReport::Failure {
-                history,
-                error_message,
-            }) => {
+            Some(TestReport::Failure(failure, run_stats)) => {
                 ui.colored_label(Color32::DARK_RED, "Обнаружены ошибки:");
 
-                ui.label("История ввода/вывода: ");
-                ui.label(format!("{}", history));
+                if ui_failure(ui, failure) {
+                    replay_seed = Some(failure.seed);
+                }
+
+                ui_stats(ui, run_stats);
+            }
+            Some(TestReport::Summary(summary)) => {
+                if summary.groups.is_empty() {
+                    ui.colored_label(Color32::GREEN, "Все тесты прошли успешно");
+                } else {
+                    ui.colored_label(Color32::DARK_RED, "Обнаружены ошибки:");
+                }
+
+                ui_summary(ui, summary);
+
+                for (i, group) in summary.groups.iter().enumerate() {
+                    let failure = &group.representative;
+                    let title = format!(
+                        "№{} ({}, повторений: {}): {}",
+                        i + 1,
+                        failure.kind,
+                        group.count,
+                        failure.signature
+                    );
+
+                    egui::CollapsingHeader::new(title)
+                        .id_salt(("failure", i))
+                        .show(ui, |ui| {
+                            if ui_failure(ui, failure) {
+                                replay_seed = Some(failure.seed);
+                            }
+                        });
+                }
 
-                ui.label(error_message.as_str());
+                ui_stats(ui, &summary.run_stats);
             }
             Some(TestReport::Error(error)) => {
                 ui.colored_label(Color32::DARK_RED, "Возникла ошибка выполнения: ");
@@ -164,6 +285,10 @@ impl AppGui {
             }
         }
 
+        if replay_seed.is_some() {
+            self.start_testing(replay_seed);
+        }
+
         self.ui_start_button(ui);
     }
 }
@@ -177,3 +302,59 @@ impl App for AppGui {
         });
     }
 }
+
+/// Returns `true` if the user wants to repeat the failed run.
+fn ui_failure(ui: &mut egui::Ui, failure: &FailureReport) -> bool {
+    ui.label("История ввода/вывода: ");
+    ui.label(format!("{}", failure.history));
+
+    ui.label(failure.error_message.as_str());
+
+    if let Some(usage) = failure.usage.as_ref() {
+        ui.label(format!("Ресурсы: {}", usage));
+    }
+
+    if let Some(minimized) = failure.minimized.as_ref() {
+        ui.separator();
+
+        ui.label("Минимизированный пример: ");
+        ui.label(format!("{}", minimized.history));
+
+        ui.label(minimized.error_message.as_str());
+
+        ui.separator();
+    }
+
+    ui.horizontal(|ui| {
+        ui.label(format!("Зерно запуска: {}", failure.seed));
+
+        ui.button("Повторить этот запуск").clicked()
+    })
+    .inner
+}
+
+fn ui_summary(ui: &mut egui::Ui, summary: &Summary) {
+    egui::Grid::new("summary").striped(true).show(ui, |ui| {
+        for (label, count) in [
+            ("Успешно", summary.passed),
+            ("Ошибки", summary.failed),
+            ("Аварийные завершения", summary.crashed),
+            ("Превышения времени", summary.timed_out),
+        ] {
+            ui.label(label);
+            ui.label(count.to_string());
+            ui.end_row();
+        }
+    });
+}
+
+fn ui_stats(ui: &mut egui::Ui, run_stats: &RunStats) {
+    if *run_stats == RunStats::default() {
+        return;
+    }
+
+    ui.collapsing("Статистика запусков", |ui| {
+        // the histogram is aligned with spaces
+        ui.monospace(run_stats.to_string());
+    });
+}
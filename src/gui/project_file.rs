@@ -0,0 +1,83 @@
+use eframe::egui::{self, Color32};
+use egui_file_dialog::{DialogMode, FileDialog};
+use final_trps::project::Project;
+use std::path::PathBuf;
+
+#[derive(Debug, Default)]
+pub struct UiProjectFile {
+    project_file: Option<PathBuf>,
+    error: Option<String>,
+    file_dialog: FileDialog,
+}
+
+impl UiProjectFile {
+    /// Returns the project the user has just opened.
+    ///
+    /// `current` is only called when the project is being saved.
+    pub fn display(
+        &mut self,
+        ctx: &egui::Context,
+        ui: &mut egui::Ui,
+        current: impl FnOnce() -> Project,
+    ) -> Option<Project> {
+        let mut save_to = None;
+
+        ui.horizontal(|ui| {
+            if ui.button("Открыть проект").clicked() {
+                self.file_dialog.pick_file();
+            }
+
+            if ui.button("Сохранить проект").clicked() {
+                match self.project_file.as_ref() {
+                    Some(path) => save_to = Some(path.clone()),
+                    None => self.file_dialog.save_file(),
+                }
+            }
+
+            if ui.button("Сохранить проект как...").clicked() {
+                self.file_dialog.save_file();
+            }
+        });
+
+        self.file_dialog.update(ctx);
+
+        let mut opened = None;
+
+        if let Some(path) = self.file_dialog.take_picked() {
+            match self.file_dialog.mode() {
+                DialogMode::SaveFile if path.extension().is_none() => {
+                    save_to = Some(path.with_extension(Project::EXTENSION));
+                }
+                DialogMode::SaveFile => save_to = Some(path),
+                _ => match Project::load(&path) {
+                    Ok(project) => {
+                        self.project_file = Some(path);
+                        self.error = None;
+                        opened = Some(project);
+                    }
+                    Err(error) => self.error = Some(format!("{error:#}")),
+                },
+            }
+        }
+
+        if let Some(path) = save_to {
+            match current().save(&path) {
+                Ok(()) => {
+                    self.project_file = Some(path);
+                    self.error = None;
+                }
+                Err(error) => self.error = Some(format!("{error:#}")),
+            }
+        }
+
+        if let Some(path) = self.project_file.as_ref() {
+            ui.label(format!("Файл проекта: {}", path.display()));
+        }
+
+        if let Some(error) = self.error.as_ref() {
+            ui.colored_label(Color32::DARK_RED, error.as_str());
+        }
+
+        opened
+    }
+}
@@ -1,5 +1,5 @@
-use super::{ContentType, RuleData, RuleType};
 use eframe::egui;
+use final_trps::rule_data::{ContentType, RuleData, RuleType};
 
 #[derive(Debug, Default)]
 pub struct UiRulePanel {
@@ -70,23 +70,69 @@ impl UiRulePanel {
                     ui.label("Тип параметра: ");
                     ui.radio_value(&mut rule.rule_type, RuleType::Input, "Входной");
                     ui.radio_value(&mut rule.rule_type, RuleType::Output, "Выходной");
+                    ui.radio_value(
+                        &mut rule.rule_type,
+                        RuleType::ErrOutput,
+                        "Выходной (stderr)",
+                    );
+                    ui.radio_value(
+                        &mut rule.rule_type,
+                        RuleType::Prompt,
+                        "Приглашение (без перевода строки)",
+                    );
+                    ui.radio_value(
+                        &mut rule.rule_type,
+                        RuleType::ErrPrompt,
+                        "Приглашение (stderr)",
+                    );
                 });
 
                 ui.horizontal(|ui| {
-                    ui.label("Тип данных: ");
-                    ui.radio_value(&mut rule.content_type, ContentType::PlainText, "Текст");
-                    ui.radio_value(
-                        &mut rule.content_type,
-                        ContentType::Regex,
-                        "Регулярное выражение",
-                    );
+                    ui.radio_value(&mut rule.rule_type, RuleType::ExitCode, "Код завершения");
+                    ui.radio_value(&mut rule.rule_type, RuleType::Signal, "Завершение сигналом");
+                });
+
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut rule.rule_type, RuleType::CloseInput, "Закрытие ввода");
                     ui.radio_value(
-                        &mut rule.content_type,
-                        ContentType::IntRanges,
-                        "Целые числа",
+                        &mut rule.rule_type,
+                        RuleType::SendSignal,
+                        "Отправка сигнала",
                     );
                 });
 
+                match rule.rule_type {
+                    RuleType::ExitCode => {
+                        ui.label("Допустимые коды завершения (например: 0, 2, 10..20):");
+                    }
+                    RuleType::Signal => {
+                        ui.label("Номера допустимых сигналов (пусто - любой сигнал):");
+                    }
+                    RuleType::CloseInput => {
+                        ui.label("Программа получит конец ввода, прочитав уже введённое");
+                        return;
+                    }
+                    RuleType::SendSignal => {
+                        ui.label("Сигнал (SIGINT, SIGTERM или SIGHUP):");
+                    }
+                    _ => {
+                        ui.horizontal(|ui| {
+                            ui.label("Тип данных: ");
+                            ui.radio_value(&mut rule.content_type, ContentType::PlainText, "Текст");
+                            ui.radio_value(
+                                &mut rule.content_type,
+                                ContentType::Regex,
+                                "Регулярное выражение",
+                            );
+                            ui.radio_value(
+                                &mut rule.content_type,
+                                ContentType::IntRanges,
+                                "Целые числа",
+                            );
+                        });
+                    }
+                }
+
                 let text_edit = egui::TextEdit::singleline(&mut rule.text)
                     .code_editor()
                     .desired_width(480.0);
@@ -154,4 +200,10 @@ impl UiRulePanel {
     pub fn rules(&self) -> &Vec<RuleData> {
         &self.rules
     }
+
+    #[inline]
+    pub fn set_rules(&mut self, rules: Vec<RuleData>) {
+        self.rules = rules;
+        self.cursor = 0;
+    }
 }
@@ -0,0 +1,487 @@
+use eframe::egui;
+use egui_file_dialog::FileDialog;
+use final_trps::{
+    communicator::CommSettings,
+    rule_data::{ArgData, ContentType},
+    run_manager::{LaunchSettings, Wrapper},
+};
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+pub struct UiSettings {
+    pub comm: CommSettings,
+    pub launch: LaunchSettings,
+    pub master_seed: Option<u64>,
+    pub time_budget: Option<Duration>,
+    pub max_run_duration: Option<Duration>,
+    pub max_peak_memory: Option<u64>,
+    pub max_cpu_time: Option<Duration>,
+    seed_text: String,
+    wrapper_text: String,
+    dir_dialog: FileDialog,
+}
+
+impl UiSettings {
+    /// Takes the settings saved in a project file.
+    pub fn open_project(&mut self, mut comm: CommSettings, launch: LaunchSettings) {
+        // the checkbox is hidden elsewhere => it couldn't be turned off
+        comm.terminal &= cfg!(target_os = "linux");
+
+        self.wrapper_text = launch
+            .wrapper
+            .as_ref()
+            .map(|wrapper| wrapper.command.join(" "))
+            .unwrap_or_default();
+        self.comm = comm;
+        self.launch = launch;
+    }
+
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        ui.collapsing("Ограничения времени", |ui| {
+            optional_duration(
+                ui,
+                "На ожидание строки вывода",
+                &mut self.comm.read_timeout,
+                CommSettings::DEFAULT_READ_TIMEOUT,
+                MAX_TIMEOUT_SECS,
+            );
+            optional_duration(
+                ui,
+                "На весь запуск программы",
+                &mut self.comm.run_timeout,
+                CommSettings::DEFAULT_RUN_TIMEOUT,
+                MAX_TIMEOUT_SECS,
+            );
+            optional_duration(
+                ui,
+                "Допустимое время работы программы (иначе - ошибка)",
+                &mut self.max_run_duration,
+                DEFAULT_MAX_RUN_DURATION,
+                MAX_TIMEOUT_SECS,
+            );
+            optional_duration(
+                ui,
+                "На всё тестирование",
+                &mut self.time_budget,
+                DEFAULT_TIME_BUDGET,
+                MAX_TIME_BUDGET_SECS,
+            );
+
+            ui.horizontal(|ui| {
+                ui.label("Пауза в выводе, завершающая приглашение:");
+
+                let mut millis = self.comm.idle_timeout.as_millis() as u64;
+                let drag_value = egui::DragValue::new(&mut millis)
+                    .range(10..=10_000)
+                    .suffix(" мс");
+
+                if ui.add(drag_value).changed() {
+                    self.comm.idle_timeout = Duration::from_millis(millis);
+                }
+            });
+        });
+
+        ui.collapsing("Потребление ресурсов", |ui| {
+            // measured after the program exits => can only fail the run
+            optional_duration(
+                ui,
+                "Допустимое время процессора (иначе - ошибка)",
+                &mut self.max_cpu_time,
+                DEFAULT_MAX_CPU_TIME,
+                MAX_TIMEOUT_SECS,
+            );
+            optional_mebibytes(
+                ui,
+                "Допустимая пиковая память (иначе - ошибка)",
+                &mut self.max_peak_memory,
+                DEFAULT_MAX_PEAK_MEMORY,
+            );
+
+            ui.separator();
+
+            // checked as the output arrives => the program is killed right away
+            optional_count(
+                ui,
+                "Длина строки вывода",
+                &mut self.comm.max_line_len,
+                CommSettings::DEFAULT_MAX_LINE_LEN,
+                " байт",
+            );
+            optional_mebibytes(
+                ui,
+                "Объём вывода за запуск",
+                &mut self.comm.max_output_len,
+                CommSettings::DEFAULT_MAX_OUTPUT_LEN,
+            );
+            optional_count(
+                ui,
+                "Количество строк вывода за запуск",
+                &mut self.comm.max_output_lines,
+                CommSettings::DEFAULT_MAX_OUTPUT_LINES,
+                "",
+            );
+
+            ui.separator();
+
+            // enforced by the system => the program notices them
+            ui.label("Системные ограничения (только Unix):");
+
+            if self.launch.effective_limits() != self.launch.limits {
+                ui.colored_label(
+                    egui::Color32::ORANGE,
+                    "Адресное пространство не ограничивается при запуске через команду-обёртку (см. параметры запуска)",
+                );
+            }
+
+            let limits = &mut self.launch.limits;
+
+            optional_mebibytes(
+                ui,
+                "Адресное пространство",
+                &mut limits.address_space,
+                DEFAULT_MAX_PEAK_MEMORY,
+            );
+            optional_count(
+                ui,
+                "Время процессора",
+                &mut limits.cpu_time,
+                DEFAULT_CPU_TIME_LIMIT,
+                " с",
+            );
+            optional_count(
+                ui,
+                "Открытые файлы",
+                &mut limits.open_files,
+                DEFAULT_OPEN_FILES_LIMIT,
+                "",
+            );
+            optional_count(
+                ui,
+                "Процессы пользователя",
+                &mut limits.processes,
+                DEFAULT_PROCESSES_LIMIT,
+                "",
+            );
+            optional_mebibytes(
+                ui,
+                "Размер записываемого файла",
+                &mut limits.file_size,
+                DEFAULT_FILE_SIZE_LIMIT,
+            );
+        });
+
+        ui.collapsing("Случайная генерация", |ui| {
+            self.display_seed(ui);
+        });
+
+        ui.collapsing("Параметры запуска", |ui| {
+            self.display_args(ui);
+
+            ui.separator();
+
+            self.display_env(ui);
+
+            ui.separator();
+
+            self.display_working_dir(ctx, ui);
+
+            ui.separator();
+
+            // only Linux has it => don't offer it elsewhere
+            if cfg!(target_os = "linux") {
+                ui.checkbox(
+                    &mut self.comm.terminal,
+                    "Запускать программу в псевдотерминале (вывод не буферизуется до завершения)",
+                );
+            }
+
+            ui.separator();
+
+            self.display_wrapper(ui);
+        });
+    }
+
+    fn display_seed(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut fixed = self.master_seed.is_some();
+
+            ui.checkbox(&mut fixed, "Фиксированное начальное зерно:");
+
+            if fixed {
+                ui.add(egui::TextEdit::singleline(&mut self.seed_text).desired_width(200.0));
+
+                match self.seed_text.trim().parse() {
+                    Ok(seed) => self.master_seed = Some(seed),
+                    Err(_) => {
+                        // keeps the last valid seed
+                        self.master_seed.get_or_insert(0);
+                        ui.colored_label(
+                            egui::Color32::ORANGE,
+                            "Зерно должно быть целым числом от 0 до 18446744073709551615",
+                        );
+                    }
+                }
+            } else {
+                self.master_seed = None;
+            }
+        });
+    }
+
+    fn display_args(&mut self, ui: &mut egui::Ui) {
+        ui.label("Аргументы командной строки:");
+
+        let mut removed = None;
+
+        for (i, arg) in self.launch.args.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt(("arg_type", i))
+                    .selected_text(arg.content_type.to_string())
+                    .show_ui(ui, |ui| {
+                        for content_type in [
+                            ContentType::PlainText,
+                            ContentType::Regex,
+                            ContentType::IntRanges,
+                        ] {
+                            ui.selectable_value(
+                                &mut arg.content_type,
+                                content_type,
+                                content_type.to_string(),
+                            );
+                        }
+                    });
+
+                let text_edit = egui::TextEdit::singleline(&mut arg.text)
+                    .code_editor()
+                    .desired_width(320.0);
+                ui.add(text_edit);
+
+                if ui.button("Удалить").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+
+        if let Some(i) = removed {
+            self.launch.args.remove(i);
+        }
+
+        if ui.button("Добавить аргумент").clicked() {
+            self.launch.args.push(ArgData::default());
+        }
+    }
+
+    fn display_env(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(
+            &mut self.launch.clear_env,
+            "Не передавать программе текущее окружение",
+        );
+
+        ui.label("Переменные окружения:");
+
+        let mut removed = None;
+
+        for (i, (key, value)) in self.launch.env.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(key).desired_width(160.0));
+                ui.label("=");
+                ui.add(egui::TextEdit::singleline(value).desired_width(240.0));
+
+                if ui.button("Удалить").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+
+        if let Some(i) = removed {
+            self.launch.env.remove(i);
+        }
+
+        if ui.button("Добавить переменную").clicked() {
+            self.launch.env.push(Default::default());
+        }
+    }
+
+    fn display_wrapper(&mut self, ui: &mut egui::Ui) {
+        let mut enabled = self.launch.wrapper.is_some();
+
+        if ui
+            .checkbox(&mut enabled, "Запускать программу через команду-обёртку")
+            .changed()
+        {
+            self.launch.wrapper = enabled.then(|| Wrapper {
+                command: Wrapper::split_command(&self.wrapper_text),
+                ..Default::default()
+            });
+        }
+
+        let Some(wrapper) = self.launch.wrapper.as_mut() else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(
+                "Команда (слова через пробел, без кавычек; путь и аргументы программы - в конце):",
+            );
+
+            let text_edit = egui::TextEdit::singleline(&mut self.wrapper_text)
+                .code_editor()
+                .hint_text("valgrind --error-exitcode=99")
+                .desired_width(320.0);
+
+            if ui.add(text_edit).changed() {
+                wrapper.command = Wrapper::split_command(&self.wrapper_text);
+            }
+        });
+
+        // the wrapper runs under the same limits as the program
+        ui.checkbox(
+            &mut wrapper.limit_address_space,
+            "Ограничивать адресное пространство и для обёртки (не подходит для valgrind)",
+        );
+
+        ui.label("Коды завершения обёртки, означающие отдельный вид ошибки:");
+
+        let mut removed = None;
+
+        for (i, (code, category)) in wrapper.exit_codes.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(code).range(0..=255));
+                ui.add(
+                    egui::TextEdit::singleline(category)
+                        .hint_text("Ошибка памяти (valgrind)")
+                        .desired_width(240.0),
+                );
+
+                if ui.button("Удалить").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+
+        if let Some(i) = removed {
+            wrapper.exit_codes.remove(i);
+        }
+
+        if ui.button("Добавить код").clicked() {
+            wrapper
+                .exit_codes
+                .push((DEFAULT_WRAPPER_EXIT_CODE, String::new()));
+        }
+    }
+
+    fn display_working_dir(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Выбрать рабочую папку").clicked() {
+                self.dir_dialog.pick_directory();
+            }
+
+            if self.launch.working_dir.is_some() && ui.button("Сбросить").clicked() {
+                self.launch.working_dir = None;
+            }
+        });
+
+        self.dir_dialog.update(ctx);
+
+        if let Some(path) = self.dir_dialog.take_picked() {
+            self.launch.working_dir = Some(path);
+        }
+
+        match self.launch.working_dir.as_ref() {
+            Some(dir) => ui.label(format!("Рабочая папка: {}", dir.display())),
+            None => ui.label("Рабочая папка: текущая"),
+        };
+    }
+}
+
+fn optional_duration(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut Option<Duration>,
+    default: Duration,
+    max_secs: f64,
+) {
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+
+        if ui.checkbox(&mut enabled, label).changed() {
+            *value = enabled.then_some(default);
+        }
+
+        if let Some(duration) = value.as_mut() {
+            let mut secs = duration.as_secs_f64();
+
+            let drag_value = egui::DragValue::new(&mut secs)
+                .range(0.01..=max_secs)
+                .speed(0.1)
+                .suffix(" с");
+
+            if ui.add(drag_value).changed() {
+                *duration = Duration::from_secs_f64(secs);
+            }
+        }
+    });
+}
+
+fn optional_count(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut Option<u64>,
+    default: u64,
+    suffix: &str,
+) {
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+
+        if ui.checkbox(&mut enabled, label).changed() {
+            *value = enabled.then_some(default);
+        }
+
+        if let Some(count) = value.as_mut() {
+            ui.add(
+                egui::DragValue::new(count)
+                    .range(1..=1 << 30)
+                    .suffix(suffix),
+            );
+        }
+    });
+}
+
+/// `value` is in bytes.
+fn optional_mebibytes(ui: &mut egui::Ui, label: &str, value: &mut Option<u64>, default: u64) {
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+
+        if ui.checkbox(&mut enabled, label).changed() {
+            *value = enabled.then_some(default);
+        }
+
+        if let Some(bytes) = value.as_mut() {
+            let mut mebibytes = *bytes >> 20;
+
+            let drag_value = egui::DragValue::new(&mut mebibytes)
+                .range(1..=1 << 20)
+                .suffix(" МиБ");
+
+            if ui.add(drag_value).changed() {
+                *bytes = mebibytes << 20;
+            }
+        }
+    });
+}
+
+const MAX_TIMEOUT_SECS: f64 = 3600.0;
+
+const DEFAULT_MAX_RUN_DURATION: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_CPU_TIME: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_PEAK_MEMORY: u64 = 256 << 20;
+
+const DEFAULT_CPU_TIME_LIMIT: u64 = 10;
+const DEFAULT_OPEN_FILES_LIMIT: u64 = 64;
+const DEFAULT_PROCESSES_LIMIT: u64 = 4096;
+const DEFAULT_FILE_SIZE_LIMIT: u64 = 16 << 20;
+
+/// The one in the `valgrind` example.
+const DEFAULT_WRAPPER_EXIT_CODE: i32 = 99;
+
+const DEFAULT_TIME_BUDGET: Duration = Duration::from_secs(600);
+const MAX_TIME_BUDGET_SECS: f64 = 7.0 * 24.0 * 3600.0;
@@ -0,0 +1,22 @@
+//! Everything that runs the tests, without the GUI.
+
+use std::sync::LazyLock;
+use time::format_description::OwnedFormatItem;
+
+pub mod communicator;
+pub mod exit_status;
+pub mod limits;
+pub mod project;
+pub mod rule_data;
+pub mod rules;
+pub mod run_manager;
+pub mod sanitizers;
+pub mod stats;
+#[cfg(target_os = "linux")]
+mod terminal;
+pub mod worker_thread;
+
+static DATE_FORMAT: LazyLock<OwnedFormatItem> = LazyLock::new(|| {
+    time::format_description::parse_owned::<2>("[year]-[month]-[day] [hour]-[minute]-[second]")
+        .unwrap()
+});
@@ -0,0 +1,184 @@
+use crate::stats;
+use bstr::ByteSlice;
+use std::process::{Command, ExitStatus};
+
+/// Limits the system enforces on the tested program (and whatever it
+/// spawns) on Unix. They are ignored elsewhere.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// In bytes.
+    pub address_space: Option<u64>,
+    /// In seconds.
+    pub cpu_time: Option<u64>,
+    pub open_files: Option<u64>,
+    /// Counts all processes of the user, not just the ones of the program.
+    pub processes: Option<u64>,
+    /// The largest file the program may write, in bytes.
+    pub file_size: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Sets the limits in the child process, right before the program starts.
+    #[cfg(unix)]
+    pub fn apply(&self, command: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        if *self == Self::default() {
+            return;
+        }
+
+        let same = |limit: Option<u64>| limit.map(|limit| (limit, limit));
+
+        let limits = [
+            (libc::RLIMIT_AS, same(self.address_space)),
+            // SIGXCPU at the soft limit tells it apart from other kills
+            (libc::RLIMIT_CPU, self.cpu_time.map(|secs| (secs, secs + 1))),
+            (libc::RLIMIT_NOFILE, same(self.open_files)),
+            (libc::RLIMIT_NPROC, same(self.processes)),
+            (libc::RLIMIT_FSIZE, same(self.file_size)),
+        ];
+
+        // only async-signal-safe calls are allowed between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                for (resource, limit) in limits {
+                    let Some((soft, hard)) = limit else {
+                        continue;
+                    };
+
+                    let limit = libc::rlimit {
+                        rlim_cur: soft as libc::rlim_t,
+                        rlim_max: hard as libc::rlim_t,
+                    };
+
+                    if libc::setrlimit(resource, &limit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply(&self, _command: &mut Command) {}
+
+    /// Tells which limit, if any, made the program fail.
+    ///
+    /// Running out of memory, files or processes only shows in the error
+    /// messages of the program, so these are guesses.
+    pub fn hit(&self, status: ExitStatus, stderr: &[u8]) -> Option<String> {
+        if status.success() {
+            return None;
+        }
+
+        #[cfg(unix)]
+        match (
+            crate::exit_status::signal(status),
+            self.cpu_time,
+            self.file_size,
+        ) {
+            (Some(libc::SIGXCPU), Some(secs), _) => {
+                return Some(format!("Превышен лимит процессорного времени ({} с)", secs));
+            }
+            (Some(libc::SIGXFSZ), _, Some(bytes)) => {
+                return Some(format!(
+                    "Превышен лимит размера записываемого файла ({})",
+                    stats::format_bytes(bytes)
+                ));
+            }
+            _ => {}
+        }
+
+        let mentions = |messages: &[&str]| messages.iter().any(|text| stderr.contains_str(text));
+
+        if let Some(bytes) = self.address_space.filter(|_| mentions(&OUT_OF_MEMORY)) {
+            return Some(format!(
+                "Вероятно, превышен лимит адресного пространства ({})",
+                stats::format_bytes(bytes)
+            ));
+        }
+
+        if let Some(count) = self.open_files.filter(|_| mentions(&TOO_MANY_FILES)) {
+            return Some(format!(
+                "Вероятно, превышен лимит открытых файлов ({})",
+                count
+            ));
+        }
+
+        self.processes
+            .filter(|_| mentions(&NO_PROCESSES))
+            .map(|count| format!("Вероятно, превышен лимит количества процессов ({})", count))
+    }
+}
+
+const OUT_OF_MEMORY: [&str; 5] = [
+    "Cannot allocate memory",
+    "bad_alloc",
+    "memory allocation of",
+    "out of memory",
+    "MemoryError",
+];
+const TOO_MANY_FILES: [&str; 1] = ["Too many open files"];
+const NO_PROCESSES: [&str; 2] = ["Resource temporarily unavailable", "Cannot fork"];
+
+//===================================================================================//
+//===================================// TESTING //===================================//
+//===================================================================================//
+
+#[cfg(all(test, unix))]
+mod test_limit_hit {
+    use super::ResourceLimits;
+    use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
+    fn limits() -> ResourceLimits {
+        ResourceLimits {
+            address_space: Some(64 << 20),
+            cpu_time: Some(2),
+            open_files: Some(16),
+            processes: None,
+            file_size: None,
+        }
+    }
+
+    fn exit_code(code: i32) -> ExitStatus {
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[test]
+    fn cpu_time_signal() {
+        let hit = limits().hit(ExitStatus::from_raw(libc::SIGXCPU), b"");
+
+        assert_eq!(
+            hit.as_deref(),
+            Some("Превышен лимит процессорного времени (2 с)")
+        );
+    }
+
+    #[test]
+    fn unset_limit_is_not_blamed() {
+        assert_eq!(limits().hit(ExitStatus::from_raw(libc::SIGXFSZ), b""), None);
+        assert_eq!(
+            limits().hit(exit_code(1), b"fork: Resource temporarily unavailable"),
+            None
+        );
+    }
+
+    #[test]
+    fn error_messages() {
+        let hit = limits().hit(
+            ExitStatus::from_raw(libc::SIGABRT),
+            b"memory allocation of 1048576 bytes failed",
+        );
+        assert!(hit.unwrap().contains("адресного пространства"));
+
+        let hit = limits().hit(exit_code(1), b"open: Too many open files\n");
+        assert!(hit.unwrap().contains("открытых файлов (16)"));
+    }
+
+    #[test]
+    fn success_is_not_a_hit() {
+        assert_eq!(limits().hit(exit_code(0), b"out of memory"), None);
+    }
+}
@@ -1,23 +1,28 @@
-use eframe::{
-    egui::{Vec2, ViewportBuilder},
-    NativeOptions,
-};
-use gui::AppGui;
-use std::sync::LazyLock;
-use time::format_description::OwnedFormatItem;
+mod cli;
+#[cfg(feature = "gui")]
+mod gui;
 
-pub mod communicator;
-pub mod gui;
-pub mod rules;
-pub mod run_manager;
-pub mod worker_thread;
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-static DATE_FORMAT: LazyLock<OwnedFormatItem> = LazyLock::new(|| {
-    time::format_description::parse_owned::<2>("[year]-[month]-[day] [hour]-[minute]-[second]")
-        .unwrap()
-});
+    // any arguments => no window
+    #[cfg(feature = "gui")]
+    if args.is_empty() {
+        run_gui();
+        return;
+    }
+
+    std::process::exit(cli::run(&args));
+}
+
+#[cfg(feature = "gui")]
+fn run_gui() {
+    use eframe::{
+        egui::{Vec2, ViewportBuilder},
+        NativeOptions,
+    };
+    use gui::AppGui;
 
-fn main() {
     let native_options = NativeOptions {
         viewport: ViewportBuilder {
             title: Some("Программа автоматизации тестирования ПО".to_owned()),
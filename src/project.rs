@@ -0,0 +1,682 @@
+use crate::{
+    communicator::CommSettings,
+    limits::ResourceLimits,
+    rule_data::{ArgData, ContentType, RuleData, RuleType},
+    run_manager::{LaunchSettings, Wrapper},
+};
+use anyhow::Context;
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Everything needed to repeat a testing session, as stored on disk.
+///
+/// The file is plain text: a header with `key = value` lines, followed by
+/// a `[rule]` section for every rule. Line breaks and backslashes in values
+/// are escaped, so every value fits on a single line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Project {
+    pub program_path: Option<PathBuf>,
+    pub rules: Vec<RuleData>,
+    pub successes_required: u32,
+    /// Runs that take longer fail, see [`crate::run_manager::TestingData`].
+    pub max_run_duration: Option<Duration>,
+    /// In bytes.
+    pub max_peak_memory: Option<u64>,
+    pub max_cpu_time: Option<Duration>,
+    pub comm_settings: CommSettings,
+    pub launch: LaunchSettings,
+}
+
+impl Project {
+    /// Bumped whenever older versions of the program can't read the file.
+    ///
+    /// 2 - resource limits.
+    /// 3 - closing stdin and sending signals.
+    /// 4 - arguments, environment, timeouts, output limits and the wrapper.
+    /// 5 - the allowed run duration, peak memory and CPU time, the address
+    ///     space limit of the wrapper, prompts on stderr.
+    pub const VERSION: u32 = 5;
+    pub const EXTENSION: &'static str = "trps";
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Не удалось прочитать файл проекта {}", path.display()))?;
+
+        Self::parse(&text).with_context(|| format!("Некорректный файл проекта {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, self.to_string())
+            .with_context(|| format!("Не удалось сохранить проект в файл {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim_start()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let mut version = None;
+        let mut project = Self {
+            program_path: None,
+            rules: Vec::new(),
+            successes_required: 1,
+            max_run_duration: None,
+            max_peak_memory: None,
+            max_cpu_time: None,
+            comm_settings: CommSettings::default(),
+            launch: LaunchSettings::default(),
+        };
+
+        for (number, line) in lines {
+            let result = if line.trim_end() == "[rule]" {
+                project.rules.push(RuleData::default());
+                Ok(())
+            } else if let Some(rule) = project.rules.last_mut() {
+                parse_rule_line(rule, line)
+            } else {
+                parse_header_line(&mut project, &mut version, line)
+            };
+
+            result.with_context(|| format!("строка {}", number))?;
+
+            if version.is_none() {
+                anyhow::bail!("строка {}: файл должен начинаться с версии формата", number);
+            }
+        }
+
+        match version {
+            Some(_) => Ok(project),
+            None => anyhow::bail!("файл пуст"),
+        }
+    }
+}
+
+impl Display for Project {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "version = {}", Self::VERSION)?;
+
+        if let Some(path) = self.program_path.as_ref() {
+            writeln!(f, "program = {}", escape(&path.to_string_lossy()))?;
+        }
+
+        writeln!(f, "successes_required = {}", self.successes_required)?;
+
+        let comm = &self.comm_settings;
+        let millis = |duration: Duration| duration.as_millis() as u64;
+
+        writeln!(
+            f,
+            "read_timeout = {}",
+            optional(comm.read_timeout.map(millis))
+        )?;
+        writeln!(
+            f,
+            "run_timeout = {}",
+            optional(comm.run_timeout.map(millis))
+        )?;
+        writeln!(f, "idle_timeout = {}", millis(comm.idle_timeout))?;
+        writeln!(
+            f,
+            "max_run_duration = {}",
+            optional(self.max_run_duration.map(millis))
+        )?;
+        writeln!(f, "max_peak_memory = {}", optional(self.max_peak_memory))?;
+        writeln!(
+            f,
+            "max_cpu_time = {}",
+            optional(self.max_cpu_time.map(millis))
+        )?;
+        writeln!(f, "max_line_len = {}", optional(comm.max_line_len))?;
+        writeln!(f, "max_output_len = {}", optional(comm.max_output_len))?;
+        writeln!(f, "max_output_lines = {}", optional(comm.max_output_lines))?;
+        writeln!(f, "terminal = {}", comm.terminal)?;
+
+        let launch = &self.launch;
+
+        for arg in launch.args.iter() {
+            let content = content_type_key(arg.content_type);
+            writeln!(f, "arg = {} {}", content, escape(&arg.text))?;
+        }
+
+        writeln!(f, "clear_env = {}", launch.clear_env)?;
+
+        for (key, value) in launch.env.iter() {
+            writeln!(f, "env = {}", escape(&format!("{}={}", key, value)))?;
+        }
+
+        if let Some(dir) = launch.working_dir.as_ref() {
+            writeln!(f, "working_dir = {}", escape(&dir.to_string_lossy()))?;
+        }
+
+        let mut limits = launch.limits;
+
+        for (key, limit) in LIMIT_KEYS {
+            if let Some(value) = *limit(&mut limits) {
+                writeln!(f, "{} = {}", key, value)?;
+            }
+        }
+
+        if let Some(wrapper) = launch.wrapper.as_ref() {
+            writeln!(f, "wrapper = {}", escape(&wrapper.command.join(" ")))?;
+
+            for (code, category) in wrapper.exit_codes.iter() {
+                writeln!(f, "wrapper_code = {} {}", code, escape(category))?;
+            }
+
+            writeln!(
+                f,
+                "wrapper_limit_address_space = {}",
+                wrapper.limit_address_space
+            )?;
+        }
+
+        for rule in self.rules.iter() {
+            writeln!(f)?;
+            writeln!(f, "[rule]")?;
+            writeln!(f, "name = {}", escape(&rule.name))?;
+            writeln!(f, "type = {}", rule_type_key(rule.rule_type))?;
+            writeln!(f, "content = {}", content_type_key(rule.content_type))?;
+            writeln!(f, "text = {}", escape(&rule.text))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_header_line(
+    project: &mut Project,
+    version: &mut Option<u32>,
+    line: &str,
+) -> anyhow::Result<()> {
+    let (key, value) = split_line(line)?;
+
+    match (key, version.is_some()) {
+        ("version", false) => {
+            let number = value
+                .trim()
+                .parse()
+                .context("версия формата должна быть целым числом")?;
+
+            // the rest of the file may mean something else in a newer format
+            if number > Project::VERSION {
+                anyhow::bail!(
+                    "файл создан более новой версией программы (версия формата {}, поддерживается до {})",
+                    number,
+                    Project::VERSION
+                );
+            }
+
+            *version = Some(number);
+        }
+        ("version", true) => anyhow::bail!("версия формата указана повторно"),
+        (_, false) => {}
+        ("program", true) => project.program_path = Some(PathBuf::from(unescape(value)?)),
+        ("successes_required", true) => {
+            project.successes_required = value
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&count| count > 0)
+                .context("количество успешных тестов должно быть положительным целым числом")?;
+        }
+        ("read_timeout", true) => {
+            project.comm_settings.read_timeout = parse_optional(value)?.map(Duration::from_millis);
+        }
+        ("run_timeout", true) => {
+            project.comm_settings.run_timeout = parse_optional(value)?.map(Duration::from_millis);
+        }
+        ("idle_timeout", true) => {
+            project.comm_settings.idle_timeout = Duration::from_millis(
+                value
+                    .trim()
+                    .parse()
+                    .context("пауза должна быть неотрицательным целым числом")?,
+            );
+        }
+        ("max_run_duration", true) => {
+            project.max_run_duration = parse_optional(value)?.map(Duration::from_millis);
+        }
+        ("max_peak_memory", true) => project.max_peak_memory = parse_optional(value)?,
+        ("max_cpu_time", true) => {
+            project.max_cpu_time = parse_optional(value)?.map(Duration::from_millis);
+        }
+        ("max_line_len", true) => project.comm_settings.max_line_len = parse_optional(value)?,
+        ("max_output_len", true) => project.comm_settings.max_output_len = parse_optional(value)?,
+        ("max_output_lines", true) => {
+            project.comm_settings.max_output_lines = parse_optional(value)?;
+        }
+        ("terminal", true) => project.comm_settings.terminal = parse_bool(value)?,
+        ("arg", true) => {
+            let (content, text) = value.split_once(' ').unwrap_or((value, ""));
+
+            project.launch.args.push(ArgData {
+                content_type: parse_content_type(content)?,
+                text: unescape(text)?,
+            });
+        }
+        ("clear_env", true) => project.launch.clear_env = parse_bool(value)?,
+        ("env", true) => {
+            let variable = unescape(value)?;
+            let (key, value) = variable
+                .split_once('=')
+                .filter(|(key, _)| !key.is_empty())
+                .context("ожидалась переменная вида «ИМЯ=значение»")?;
+
+            project.launch.env.push((key.to_owned(), value.to_owned()));
+        }
+        ("working_dir", true) => {
+            project.launch.working_dir = Some(PathBuf::from(unescape(value)?));
+        }
+        ("wrapper", true) => {
+            project.launch.wrapper = Some(Wrapper {
+                command: Wrapper::split_command(&unescape(value)?),
+                ..Default::default()
+            });
+        }
+        ("wrapper_limit_address_space", true) => {
+            project
+                .launch
+                .wrapper
+                .as_mut()
+                .context("ограничение обёртки указано раньше команды-обёртки")?
+                .limit_address_space = parse_bool(value)?;
+        }
+        ("wrapper_code", true) => {
+            let wrapper = project
+                .launch
+                .wrapper
+                .as_mut()
+                .context("код завершения указан раньше команды-обёртки")?;
+
+            let (code, category) = value.split_once(' ').unwrap_or((value, ""));
+            let code = code
+                .parse()
+                .context("код завершения должен быть целым числом")?;
+
+            wrapper.exit_codes.push((code, unescape(category)?));
+        }
+        (key, true) => {
+            let (_, limit) = LIMIT_KEYS
+                .iter()
+                .find(|(name, _)| *name == key)
+                .with_context(|| format!("неизвестный параметр «{}»", key))?;
+
+            *limit(&mut project.launch.limits) = Some(
+                value
+                    .trim()
+                    .parse()
+                    .context("ограничение должно быть неотрицательным целым числом")?,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_rule_line(rule: &mut RuleData, line: &str) -> anyhow::Result<()> {
+    let (key, value) = split_line(line)?;
+
+    match key {
+        "name" => rule.name = unescape(value)?,
+        "type" => {
+            rule.rule_type = RULE_TYPES
+                .iter()
+                .find(|(_, name)| *name == value.trim())
+                .map(|(rule_type, _)| *rule_type)
+                .with_context(|| format!("неизвестный тип правила «{}»", value.trim()))?;
+        }
+        "content" => rule.content_type = parse_content_type(value)?,
+        "text" => rule.text = unescape(value)?,
+        key => anyhow::bail!("неизвестный параметр правила «{}»", key),
+    }
+
+    Ok(())
+}
+
+#[inline]
+fn split_line(line: &str) -> anyhow::Result<(&str, &str)> {
+    let (key, value) = line
+        .split_once('=')
+        .context("ожидалась строка вида «параметр = значение»")?;
+
+    // the space after `=` belongs to the syntax, the rest to the value
+    Ok((key.trim(), value.strip_prefix(' ').unwrap_or(value)))
+}
+
+#[inline]
+fn parse_content_type(value: &str) -> anyhow::Result<ContentType> {
+    CONTENT_TYPES
+        .iter()
+        .find(|(_, name)| *name == value.trim())
+        .map(|(content_type, _)| *content_type)
+        .with_context(|| format!("неизвестный тип данных «{}»", value.trim()))
+}
+
+#[inline]
+fn parse_bool(value: &str) -> anyhow::Result<bool> {
+    match value.trim() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => anyhow::bail!("ожидалось «true» или «false»"),
+    }
+}
+
+/// `none` stands for no limit.
+#[inline]
+fn parse_optional(value: &str) -> anyhow::Result<Option<u64>> {
+    match value.trim() {
+        "none" => Ok(None),
+        value => value
+            .parse()
+            .map(Some)
+            .context("ожидалось неотрицательное целое число или «none»"),
+    }
+}
+
+#[inline]
+fn optional(value: Option<u64>) -> String {
+    value.map_or_else(|| "none".to_owned(), |value| value.to_string())
+}
+
+type LimitField = fn(&mut ResourceLimits) -> &mut Option<u64>;
+
+/// Sizes are in bytes, CPU time in seconds. The timeouts of the header
+/// are in milliseconds.
+const LIMIT_KEYS: [(&str, LimitField); 5] = [
+    ("limit_address_space", |limits| &mut limits.address_space),
+    ("limit_cpu_time", |limits| &mut limits.cpu_time),
+    ("limit_open_files", |limits| &mut limits.open_files),
+    ("limit_processes", |limits| &mut limits.processes),
+    ("limit_file_size", |limits| &mut limits.file_size),
+];
+
+const RULE_TYPES: [(RuleType, &str); 9] = [
+    (RuleType::Input, "input"),
+    (RuleType::Output, "output"),
+    (RuleType::ErrOutput, "stderr"),
+    (RuleType::Prompt, "prompt"),
+    (RuleType::ErrPrompt, "stderr_prompt"),
+    (RuleType::ExitCode, "exit_code"),
+    (RuleType::Signal, "signal"),
+    (RuleType::CloseInput, "close_stdin"),
+    (RuleType::SendSignal, "send_signal"),
+];
+
+const CONTENT_TYPES: [(ContentType, &str); 3] = [
+    (ContentType::PlainText, "text"),
+    (ContentType::Regex, "regex"),
+    (ContentType::IntRanges, "int_ranges"),
+];
+
+#[inline]
+fn rule_type_key(rule_type: RuleType) -> &'static str {
+    RULE_TYPES
+        .iter()
+        .find(|(other, _)| *other == rule_type)
+        .map(|(_, name)| *name)
+        .unwrap()
+}
+
+#[inline]
+fn content_type_key(content_type: ContentType) -> &'static str {
+    CONTENT_TYPES
+        .iter()
+        .find(|(other, _)| *other == content_type)
+        .map(|(_, name)| *name)
+        .unwrap()
+}
+
+fn escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+
+    for ch in value.chars() {
+        match ch {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            ch => result.push(ch),
+        }
+    }
+
+    result
+}
+
+fn unescape(value: &str) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some('\\') => result.push('\\'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(other) => anyhow::bail!("неизвестная escape-последовательность «\\{}»", other),
+            None => anyhow::bail!("строка обрывается на «\\»"),
+        }
+    }
+
+    Ok(result)
+}
+
+//===================================================================================//
+//===================================// TESTING //===================================//
+//===================================================================================//
+
+#[cfg(test)]
+mod test_project_file {
+    use super::Project;
+    use crate::{
+        communicator::CommSettings,
+        limits::ResourceLimits,
+        rule_data::{ArgData, ContentType, RuleData, RuleType},
+        run_manager::{LaunchSettings, Wrapper},
+    };
+    use std::time::Duration;
+
+    fn project() -> Project {
+        Project {
+            program_path: Some("/usr/bin/my program".into()),
+            rules: vec![
+                RuleData {
+                    name: "Приглашение".to_owned(),
+                    rule_type: RuleType::Prompt,
+                    content_type: ContentType::PlainText,
+                    text: "Enter N: ".to_owned(),
+                },
+                RuleData {
+                    name: "число".to_owned(),
+                    rule_type: RuleType::Input,
+                    content_type: ContentType::IntRanges,
+                    text: "1..10,\n-5".to_owned(),
+                },
+                RuleData {
+                    name: " = tricky \\ name ".to_owned(),
+                    rule_type: RuleType::ErrOutput,
+                    content_type: ContentType::Regex,
+                    text: r"\d+\s*\\".to_owned(),
+                },
+                RuleData {
+                    name: "Пароль".to_owned(),
+                    rule_type: RuleType::ErrPrompt,
+                    content_type: ContentType::PlainText,
+                    text: "Password: ".to_owned(),
+                },
+                RuleData {
+                    name: "Ctrl+C".to_owned(),
+                    rule_type: RuleType::SendSignal,
+                    content_type: ContentType::PlainText,
+                    text: "SIGINT".to_owned(),
+                },
+                RuleData {
+                    rule_type: RuleType::CloseInput,
+                    ..Default::default()
+                },
+                RuleData {
+                    rule_type: RuleType::Signal,
+                    ..Default::default()
+                },
+            ],
+            successes_required: 250,
+            max_run_duration: Some(Duration::from_millis(750)),
+            max_peak_memory: Some(96 << 20),
+            max_cpu_time: None,
+            comm_settings: CommSettings {
+                read_timeout: Some(Duration::from_millis(1500)),
+                run_timeout: None,
+                idle_timeout: Duration::from_millis(50),
+                terminal: true,
+                max_line_len: None,
+                max_output_len: Some(1 << 20),
+                max_output_lines: Some(5000),
+            },
+            launch: LaunchSettings {
+                args: vec![
+                    ArgData {
+                        content_type: ContentType::PlainText,
+                        text: "--name".to_owned(),
+                    },
+                    ArgData {
+                        content_type: ContentType::Regex,
+                        text: "[a-z ]{1,8}".to_owned(),
+                    },
+                    ArgData {
+                        content_type: ContentType::PlainText,
+                        text: String::new(),
+                    },
+                ],
+                env: vec![
+                    ("LANG".to_owned(), "C".to_owned()),
+                    ("EQUATION".to_owned(), "a = b\nc".to_owned()),
+                ],
+                clear_env: true,
+                working_dir: Some("/tmp/work dir".into()),
+                limits: ResourceLimits {
+                    address_space: Some(256 << 20),
+                    cpu_time: Some(2),
+                    processes: Some(0),
+                    ..Default::default()
+                },
+                wrapper: Some(Wrapper {
+                    command: vec!["valgrind".to_owned(), "--error-exitcode=99".to_owned()],
+                    exit_codes: vec![
+                        (99, "Ошибка памяти (valgrind)".to_owned()),
+                        (2, String::new()),
+                    ],
+                    limit_address_space: false,
+                }),
+            },
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let project = project();
+
+        assert_eq!(Project::parse(&project.to_string()).unwrap(), project);
+    }
+
+    #[test]
+    fn no_program() {
+        let project = Project {
+            program_path: None,
+            ..project()
+        };
+
+        assert_eq!(Project::parse(&project.to_string()).unwrap(), project);
+    }
+
+    #[test]
+    fn older_version() {
+        let project = Project::parse("version = 3\nlimit_cpu_time = 1\n").unwrap();
+
+        assert_eq!(project.comm_settings, CommSettings::default());
+        assert_eq!(project.max_run_duration, None);
+        assert_eq!(project.max_peak_memory, None);
+        assert_eq!(project.launch.limits.cpu_time, Some(1));
+        assert_eq!(project.launch.wrapper, None);
+    }
+
+    #[test]
+    fn wrapper_limits() {
+        let project = Project::parse(
+            "version = 5\nwrapper = taskset -c 0\nwrapper_limit_address_space = true",
+        )
+        .unwrap();
+
+        assert!(project.launch.wrapper.unwrap().limit_address_space);
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let project = Project::parse(
+            "# project\nversion = 1\n\n  successes_required = 3\n\n[rule]\n# x\ntext = a",
+        )
+        .unwrap();
+
+        assert_eq!(project.successes_required, 3);
+        assert_eq!(project.rules.len(), 1);
+        assert_eq!(project.rules[0].text, "a");
+    }
+
+    #[test]
+    fn newer_version() {
+        let error = Project::parse("version = 999\nsomething_new = 1\n").unwrap_err();
+
+        assert!(format!("{error:#}").contains("более новой версией"));
+    }
+
+    #[test]
+    fn missing_version() {
+        assert!(Project::parse("successes_required = 3\n").is_err());
+        assert!(Project::parse("").is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        for text in [
+            "version = one",
+            "version = 1\nversion = 1",
+            "version = 1\nsuccesses_required = 0",
+            "version = 1\nwhat is this",
+            "version = 1\n[rule]\ntype = sometimes",
+            "version = 1\n[rule]\ncontent = pictures",
+            "version = 1\n[rule]\ntext = \\q",
+            "version = 1\n[rule]\nsize = 2",
+            "version = 2\nlimit_open_files = many",
+            "version = 2\nlimit_open_files = -1",
+            "version = 4\nread_timeout = soon",
+            "version = 4\nidle_timeout = none",
+            "version = 4\nterminal = yes",
+            "version = 4\narg = pictures x",
+            "version = 4\nenv = NOVALUE",
+            "version = 4\nenv = =value",
+            "version = 4\nwrapper_code = 99 memory",
+            "version = 4\nwrapper = valgrind\nwrapper_code = many",
+            "version = 5\nmax_run_duration = -1",
+            "version = 5\nmax_peak_memory = 64M",
+            "version = 5\nmax_cpu_time = soon",
+            "version = 5\nwrapper_limit_address_space = true",
+            "version = 5\nwrapper = taskset\nwrapper_limit_address_space = 1",
+        ] {
+            assert!(Project::parse(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn error_has_line_number() {
+        let error = Project::parse("version = 1\n\n[rule]\ntype = sometimes").unwrap_err();
+
+        assert!(format!("{error:#}").contains("строка 4"));
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Hash)]
+pub enum RuleType {
+    #[default]
+    Input,
+    Output,
+    ErrOutput,
+    Prompt,
+    ErrPrompt,
+    ExitCode,
+    Signal,
+    /// Closes the program's stdin, so that it sees the end of its input.
+    CloseInput,
+    /// Sends the program a signal, like Ctrl+C does.
+    SendSignal,
+}
+
+impl Display for RuleType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Input => write!(f, "входное"),
+            Self::Output => write!(f, "выходное"),
+            Self::ErrOutput => write!(f, "выходное, stderr"),
+            Self::Prompt => write!(f, "приглашение"),
+            Self::ErrPrompt => write!(f, "приглашение, stderr"),
+            Self::ExitCode => write!(f, "код завершения"),
+            Self::Signal => write!(f, "сигнал завершения"),
+            Self::CloseInput => write!(f, "закрытие ввода"),
+            Self::SendSignal => write!(f, "отправка сигнала"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Hash)]
+pub enum ContentType {
+    #[default]
+    PlainText,
+    Regex,
+    IntRanges,
+}
+
+impl Display for ContentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PlainText => write!(f, "Текст"),
+            Self::Regex => write!(f, "Регулярное выражение"),
+            Self::IntRanges => write!(f, "Целые числа"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuleData {
+    pub name: String,
+    pub rule_type: RuleType,
+    pub content_type: ContentType,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArgData {
+    pub content_type: ContentType,
+    pub text: String,
+}
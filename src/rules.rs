@@ -1,21 +1,72 @@
 use crate::worker_thread::OpReport;
 use bstr::{BString, ByteSlice, ByteVec};
-use rand::{
-    rngs::ThreadRng,
-    seq::{IteratorRandom, SliceRandom},
-    Rng,
-};
+use rand::{Rng, RngCore};
 use regex::bytes::{Regex, RegexBuilder};
 use regex_syntax::hir::{Class, ClassBytes, ClassUnicode, Hir, HirKind};
 use std::{fmt::Debug, ops::RangeInclusive};
 
-pub trait Rule: Debug {
+pub trait Rule: Debug + Send + Sync {
     fn parse(text: &str) -> anyhow::Result<Self>
     where
         Self: Sized;
 
     fn validate(&self, text: &BString) -> OpReport;
-    fn generate(&self) -> anyhow::Result<BString>;
+    fn generate_from(&self, choices: &mut Choices) -> anyhow::Result<BString>;
+
+    #[inline]
+    fn generate(&self, rng: &mut dyn RngCore) -> anyhow::Result<BString> {
+        self.generate_from(&mut Choices::random(rng))
+    }
+}
+
+/// The decisions made while generating a value.
+///
+/// Every decision is a number from `0..=max`, and smaller numbers always
+/// give simpler values. Recorded decisions can be replayed (and lowered)
+/// to shrink a failing input.
+pub struct Choices<'a> {
+    rng: Option<&'a mut dyn RngCore>,
+    replayed: &'a [u128],
+    picks: Vec<u128>,
+}
+
+impl<'a> Choices<'a> {
+    #[inline]
+    pub fn random(rng: &'a mut dyn RngCore) -> Self {
+        Self {
+            rng: Some(rng),
+            replayed: &[],
+            picks: Vec::new(),
+        }
+    }
+
+    /// Decisions past the end of `picks` are the simplest ones.
+    #[inline]
+    pub fn replay(picks: &'a [u128]) -> Self {
+        Self {
+            rng: None,
+            replayed: picks,
+            picks: Vec::new(),
+        }
+    }
+
+    pub fn pick(&mut self, max: u128) -> u128 {
+        let pick = match (self.replayed.get(self.picks.len()), self.rng.as_mut()) {
+            (Some(&pick), _) => pick.min(max),
+            (None, Some(rng)) => rng.gen_range(0..=max),
+            (None, None) => 0,
+        };
+
+        self.picks.push(pick);
+
+        pick
+    }
+
+    /// The decisions that were actually used.
+    #[inline]
+    pub fn into_picks(self) -> Vec<u128> {
+        self.picks
+    }
 }
 
 #[derive(Debug)]
@@ -42,7 +93,7 @@ impl Rule for PlainText {
     }
 
     #[inline]
-    fn generate(&self) -> anyhow::Result<BString> {
+    fn generate_from(&self, _choices: &mut Choices) -> anyhow::Result<BString> {
         Ok(BString::from(self.text.as_str()))
     }
 
@@ -73,7 +124,7 @@ impl RegExpr {
         )
     }
 
-    fn generate_regex_item(hir: &Hir) -> anyhow::Result<Item> {
+    fn generate_regex_item(hir: &Hir) -> anyhow::Result<Item<'_>> {
         match hir.kind() {
             HirKind::Empty => Ok(Item::Literal(BString::from(""))),
             HirKind::Literal(lit) => Ok(Item::Literal(lit.0.to_vec().into())),
@@ -137,11 +188,10 @@ impl Rule for RegExpr {
         Ok(Self { regex, syntax })
     }
 
-    fn generate(&self) -> anyhow::Result<BString> {
-        let mut rng = rand::thread_rng();
+    fn generate_from(&self, choices: &mut Choices) -> anyhow::Result<BString> {
         let mut result = BString::from("");
 
-        Self::generate_regex_item(&self.syntax)?.append_to(&mut result, &mut rng);
+        Self::generate_regex_item(&self.syntax)?.append_to(&mut result, choices);
 
         Ok(result)
     }
@@ -169,48 +219,92 @@ enum Item<'a> {
 }
 
 impl Item<'_> {
-    fn append_to(&self, string: &mut BString, rng: &mut ThreadRng) {
+    fn append_to(&self, string: &mut BString, choices: &mut Choices) {
         match self {
             Self::Literal(lit) => {
                 string.extend_from_slice(&lit[..]);
             }
             Self::ByteChoice(bytes) => {
-                if let Some(byte) = bytes
+                let total: u128 = bytes
                     .iter()
-                    .flat_map(|range| range.start()..=range.end())
-                    .choose(rng)
-                {
-                    string.push_byte(byte);
+                    .map(|range| (range.end() - range.start()) as u128 + 1)
+                    .sum();
+
+                if total > 0 {
+                    let mut index = choices.pick(total - 1);
+
+                    for range in bytes.iter() {
+                        let len = (range.end() - range.start()) as u128 + 1;
+
+                        if index < len {
+                            string.push_byte(range.start() + index as u8);
+                            break;
+                        }
+
+                        index -= len;
+                    }
                 }
             }
             Self::CharChoice(chars) => {
-                if let Some(ch) = chars
+                let total: u128 = chars
                     .iter()
-                    .flat_map(|range| range.start()..=range.end())
-                    .choose(rng)
-                {
-                    string.push_char(ch);
+                    .map(|range| char_count(range.start(), range.end()))
+                    .sum();
+
+                if total > 0 {
+                    let mut index = choices.pick(total - 1);
+
+                    for range in chars.iter() {
+                        let len = char_count(range.start(), range.end());
+
+                        if index < len {
+                            if let Some(ch) = (range.start()..=range.end()).nth(index as usize) {
+                                string.push_char(ch);
+                            }
+                            break;
+                        }
+
+                        index -= len;
+                    }
                 }
             }
             Self::Repeat(item, range) => {
-                for _i in 0..rng.gen_range(range.clone()) {
-                    item.append_to(string, rng);
+                let extra = choices.pick((range.end() - range.start()) as u128) as u32;
+
+                for _i in 0..range.start() + extra {
+                    item.append_to(string, choices);
                 }
             }
             Self::Seq(seq) => {
                 for item in seq.iter() {
-                    item.append_to(string, rng);
+                    item.append_to(string, choices);
                 }
             }
-            Self::AnyOf(choices) => {
-                if let Some(item) = choices.choose(rng) {
-                    item.append_to(string, rng);
+            Self::AnyOf(alternatives) => {
+                if !alternatives.is_empty() {
+                    let index = choices.pick(alternatives.len() as u128 - 1);
+
+                    alternatives[index as usize].append_to(string, choices);
                 }
             }
         }
     }
 }
 
+/// Number of chars in `start..=end`, without the surrogate gap.
+#[inline]
+fn char_count(start: char, end: char) -> u128 {
+    const SURROGATES: RangeInclusive<u32> = 0xD800..=0xDFFF;
+
+    let (start, end) = (start as u32, end as u32);
+    let overlap = end
+        .min(*SURROGATES.end())
+        .saturating_add(1)
+        .saturating_sub(start.max(*SURROGATES.start()));
+
+    (end - start + 1 - overlap) as u128
+}
+
 #[derive(Debug)]
 pub struct IntRanges {
     ranges: Vec<RangeInclusive<i64>>,
@@ -226,6 +320,16 @@ impl IntRanges {
         )
     }
 
+    #[inline]
+    pub fn contains(&self, num: i64) -> bool {
+        self.ranges.iter().any(|range| range.contains(&num))
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.orig_text
+    }
+
     #[inline]
     fn parse_int(s: &str, line: &str) -> anyhow::Result<i64> {
         match s.parse() {
@@ -282,24 +386,33 @@ impl Rule for IntRanges {
         }
     }
 
-    fn generate(&self) -> anyhow::Result<BString> {
-        let mut rng = rand::thread_rng();
+    fn generate_from(&self, choices: &mut Choices) -> anyhow::Result<BString> {
+        // lower numbers come from smaller choices
+        let mut ranges = self.ranges.clone();
+        ranges.sort_by_key(|range| *range.start());
 
-        let range = self
-            .ranges
-            .choose_weighted(&mut rng, |range| {
-                (range.end().wrapping_sub(*range.start()).unsigned_abs() as u128).saturating_add(1)
-            })
-            .unwrap();
-        let num = range.clone().choose(&mut rng).unwrap();
+        let len = |range: &RangeInclusive<i64>| range.end().abs_diff(*range.start()) as u128 + 1;
+        let total: u128 = ranges.iter().map(len).sum();
+
+        let mut index = choices.pick(total - 1);
+
+        for range in ranges.iter() {
+            if index < len(range) {
+                let num = (*range.start() as i128 + index as i128) as i64;
+
+                return Ok(BString::new(num.to_string().into()));
+            }
+
+            index -= len(range);
+        }
 
-        Ok(BString::new(num.to_string().into()))
+        unreachable!("the choice is within the ranges")
     }
 
     fn validate(&self, text: &BString) -> OpReport {
         match text.to_str_lossy().parse() {
             Ok(num) => {
-                if self.ranges.iter().any(|range| range.contains(&num)) {
+                if self.contains(num) {
                     OpReport::Success
                 } else {
                     OpReport::Failure {
@@ -526,7 +639,7 @@ mod test_int_gen {
         };
 
         for _i in 0..1000 {
-            let n = ranges.generate().unwrap();
+            let n = ranges.generate(&mut rng).unwrap();
             assert_eq!(ranges.validate(&n), OpReport::Success);
         }
     }
@@ -536,11 +649,12 @@ mod test_int_gen {
 mod test_regex_generation {
     use super::{RegExpr, Rule};
     use crate::worker_thread::OpReport;
+    use rand::{rngs::StdRng, SeedableRng};
 
     fn check(input: &str) {
         let regex = RegExpr::parse(input).unwrap();
 
-        let generated = regex.generate().unwrap();
+        let generated = regex.generate(&mut rand::thread_rng()).unwrap();
 
         match regex.validate(&generated) {
             OpReport::Success => {}
@@ -551,7 +665,7 @@ mod test_regex_generation {
     fn check_invalid(input: &str) {
         let regex = RegExpr::parse(input).unwrap();
 
-        assert!(regex.generate().is_err());
+        assert!(regex.generate(&mut rand::thread_rng()).is_err());
     }
 
     #[test]
@@ -576,8 +690,71 @@ mod test_regex_generation {
         );
     }
 
+    #[test]
+    fn same_seed_same_output() {
+        let regex = RegExpr::parse(r"[a-z]{5,30}( \d+)*").unwrap();
+
+        for seed in 0..100 {
+            let first = regex.generate(&mut StdRng::seed_from_u64(seed)).unwrap();
+            let second = regex.generate(&mut StdRng::seed_from_u64(seed)).unwrap();
+
+            assert_eq!(first, second);
+        }
+    }
+
     #[test]
     fn regex_complex() {
         check_invalid(r".*[(0-9A-Xa-mz)&&[^MNO]]{10,20} ;\b(\P{Greek}|\d)+");
     }
 }
+
+#[cfg(test)]
+mod test_choices {
+    use super::{Choices, IntRanges, RegExpr, Rule};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn simplest(rule: &dyn Rule) -> String {
+        rule.generate_from(&mut Choices::replay(&[]))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn simplest_regex() {
+        let regex = RegExpr::parse(r"[b-z]{3,10}(x|yy)\d*").unwrap();
+
+        assert_eq!(simplest(&regex), "bbbx");
+    }
+
+    #[test]
+    fn simplest_int() {
+        let ranges = IntRanges::parse("5..10, -3..0").unwrap();
+
+        assert_eq!(simplest(&ranges), "-3");
+    }
+
+    #[test]
+    fn replay_clamps_picks() {
+        let ranges = IntRanges::parse("1..3").unwrap();
+
+        let generated = ranges.generate_from(&mut Choices::replay(&[100])).unwrap();
+
+        assert_eq!(generated, "3");
+    }
+
+    #[test]
+    fn replay_repeats_random() {
+        let regex = RegExpr::parse(r"([a-z]{2,8}|\p{Greek}+) \d{1,4}").unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for _i in 0..100 {
+            let mut choices = Choices::random(&mut rng);
+            let generated = regex.generate_from(&mut choices).unwrap();
+            let picks = choices.into_picks();
+
+            let replayed = regex.generate_from(&mut Choices::replay(&picks)).unwrap();
+
+            assert_eq!(generated, replayed);
+        }
+    }
+}
@@ -1,11 +1,21 @@
-use crate::{communicator::History, gui::RuleData, worker_thread::Runner};
+use crate::{
+    communicator::{CancelToken, CommSettings, History, ResourceUsage},
+    limits::ResourceLimits,
+    rule_data::{ArgData, RuleData},
+    stats::RunStats,
+    worker_thread::Runner,
+};
 use std::{
+    fmt::Display,
     path::PathBuf,
+    process::ExitStatus,
     sync::{
         atomic::{AtomicU32, Ordering},
         mpsc::{self, Receiver, SyncSender},
-        Arc,
+        Arc, Mutex,
     },
+    thread,
+    time::{Duration, Instant},
 };
 
 #[derive(Debug)]
@@ -33,7 +43,8 @@ impl RunManager {
     }
 
     fn restart_thread(&mut self) {
-        self.work_state.reset();
+        // the old runner may still be winding down => it keeps its own state
+        self.work_state = Arc::new(SharedRunnerState::default());
         self.last_report = None;
 
         let (work_sender, work_receiver) = mpsc::sync_channel::<TestingData>(0);
@@ -47,6 +58,8 @@ impl RunManager {
 
     #[inline]
     pub fn force_stop_thread(&mut self) {
+        self.work_state.cancel.cancel();
+
         self.restart_thread();
         self.last_report = None;
     }
@@ -83,13 +96,68 @@ impl RunManager {
 pub struct SharedRunnerState {
     pub solved_tests: AtomicU32,
     pub required_tests: AtomicU32,
+    pub passed: AtomicU32,
+    pub failed: AtomicU32,
+    pub crashed: AtomicU32,
+    pub timed_out: AtomicU32,
+    /// `None` until the testing starts.
+    pub start_time: Mutex<Option<Instant>>,
+    pub cancel: Arc<CancelToken>,
 }
 
 impl SharedRunnerState {
     #[inline]
     pub fn reset(&self) {
-        self.solved_tests.store(0, Ordering::Release);
-        self.required_tests.store(0, Ordering::Release);
+        for counter in [
+            &self.solved_tests,
+            &self.required_tests,
+            &self.passed,
+            &self.failed,
+            &self.crashed,
+            &self.timed_out,
+        ] {
+            counter.store(0, Ordering::Release);
+        }
+
+        *self.start_time.lock().unwrap() = None;
+    }
+
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.start_time
+            .lock()
+            .unwrap()
+            .map_or(Duration::ZERO, |start_time| start_time.elapsed())
+    }
+
+    /// Runs that are done, whatever their result.
+    #[inline]
+    pub fn finished_runs(&self) -> u32 {
+        [&self.passed, &self.failed, &self.crashed, &self.timed_out]
+            .into_iter()
+            .map(|counter| counter.load(Ordering::Acquire))
+            .sum()
+    }
+
+    /// Finished runs per second.
+    #[inline]
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed().as_secs_f64();
+
+        if secs > 0.0 {
+            self.finished_runs() as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    #[inline]
+    pub fn counter(&self, kind: FailureKind) -> &AtomicU32 {
+        match kind {
+            FailureKind::Failed => &self.failed,
+            FailureKind::Crashed => &self.crashed,
+            FailureKind::TimedOut => &self.timed_out,
+        }
     }
 }
 
@@ -98,18 +166,190 @@ pub struct TestingData {
     pub program_path: PathBuf,
     pub rules: Vec<RuleData>,
     pub successes_required: u32,
+    /// How many runs may go at the same time.
+    pub workers: u32,
+    /// Do all runs and report every failure instead of stopping at the first one.
+    pub continue_on_failure: bool,
+    /// No runs are started after this much time, even if
+    /// `successes_required` isn't reached yet.
+    pub time_budget: Option<Duration>,
+    /// Runs that take longer fail, even if the output is right.
+    pub max_run_duration: Option<Duration>,
+    /// In bytes. Only checked where the usage can be measured, like `max_cpu_time`.
+    pub max_peak_memory: Option<u64>,
+    /// User and system time together.
+    pub max_cpu_time: Option<Duration>,
+    pub comm_settings: CommSettings,
+    pub launch: LaunchSettings,
+    /// Random if not set.
+    pub master_seed: Option<u64>,
+    /// Runs a single test generated from this seed.
+    pub replay_seed: Option<u64>,
+}
+
+/// One worker for every CPU core.
+pub fn default_workers() -> u32 {
+    thread::available_parallelism().map_or(1, |count| count.get() as u32)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LaunchSettings {
+    pub args: Vec<ArgData>,
+    pub env: Vec<(String, String)>,
+    pub clear_env: bool,
+    pub working_dir: Option<PathBuf>,
+    /// Apply to the wrapper too, see [`LaunchSettings::effective_limits`].
+    pub limits: ResourceLimits,
+    pub wrapper: Option<Wrapper>,
+}
+
+impl LaunchSettings {
+    /// The wrapper gets the limits along with the program, and tools like
+    /// `valgrind` need far more address space than what they run => that one
+    /// is dropped unless the wrapper allows it.
+    #[inline]
+    pub fn effective_limits(&self) -> ResourceLimits {
+        match self.wrapper.as_ref() {
+            Some(wrapper) if !wrapper.limit_address_space => ResourceLimits {
+                address_space: None,
+                ..self.limits
+            },
+            _ => self.limits,
+        }
+    }
+}
+
+/// A command the program is run under, like `valgrind` or `taskset`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Wrapper {
+    /// The wrapper and its arguments. The program with its own arguments
+    /// goes after them.
+    pub command: Vec<String>,
+    /// Exit codes of the wrapper that stand for a failure of their own,
+    /// e.g. 99 for `valgrind --error-exitcode=99`.
+    pub exit_codes: Vec<(i32, String)>,
+    /// Off for tools that take much more memory than the program, like `valgrind`.
+    pub limit_address_space: bool,
+}
+
+impl Wrapper {
+    /// The words of `text`, split on whitespace. Quotes aren't special =>
+    /// an argument can't contain spaces.
+    #[inline]
+    pub fn split_command(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_owned).collect()
+    }
+
+    /// The failure category the wrapper reported, if any.
+    pub fn category(&self, status: ExitStatus) -> Option<&str> {
+        let code = status.code()?;
+
+        self.exit_codes
+            .iter()
+            .find(|(other, _)| *other == code)
+            .map(|(_, category)| category.as_str())
+            .filter(|category| !category.is_empty())
+    }
 }
 
 #[derive(Debug)]
 pub enum TestReport {
-    Success,
-    Failure {
-        history: History,
-        error_message: String,
-    },
+    Success(RunStats),
+    Failure(FailureReport, RunStats),
+    /// All runs were done despite the failures.
+    Summary(Summary),
     Error(anyhow::Error),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FailureKind {
+    Failed,
+    /// The program was killed by a signal it wasn't expected to get.
+    Crashed,
+    TimedOut,
+}
+
+impl Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Failed => write!(f, "ошибка"),
+            Self::Crashed => write!(f, "аварийное завершение"),
+            Self::TimedOut => write!(f, "превышение времени"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FailureReport {
+    pub history: History,
+    pub error_message: String,
+    pub seed: u64,
+    pub kind: FailureKind,
+    pub signature: FailureSignature,
+    /// `None` if the program wasn't reaped or it can't be measured.
+    pub usage: Option<ResourceUsage>,
+    pub minimized: Option<Minimized>,
+}
+
+/// Failures with the same signature most likely come from the same bug.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FailureSignature {
+    /// `None` if the program failed after all the steps were done.
+    pub step: Option<usize>,
+    pub rule_name: Option<String>,
+    /// The first line of the error message, which doesn't mention the output.
+    pub message_kind: String,
+    /// `None` if the program was still running.
+    pub exit: Option<String>,
+}
+
+impl Display for FailureSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(step) = self.step {
+            write!(f, "шаг №{}", step + 1)?;
+
+            if let Some(name) = self.rule_name.as_ref() {
+                write!(f, " («{}»)", name)?;
+            }
+
+            write!(f, ": ")?;
+        }
+
+        write!(f, "{}", self.message_kind)?;
+
+        match self.exit.as_ref() {
+            Some(exit) if !self.message_kind.contains(exit.as_str()) => write!(f, " [{}]", exit),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub passed: u32,
+    pub failed: u32,
+    pub crashed: u32,
+    pub timed_out: u32,
+    /// The most common failures first.
+    pub groups: Vec<FailureGroup>,
+    pub run_stats: RunStats,
+}
+
+/// All failures with the same signature.
+#[derive(Debug)]
+pub struct FailureGroup {
+    pub count: u32,
+    /// The failure with the simplest inputs, minimized.
+    pub representative: FailureReport,
+}
+
+/// The simplest inputs found that still make the program fail.
+#[derive(Debug)]
+pub struct Minimized {
+    pub history: History,
+    pub error_message: String,
+}
+
 impl From<anyhow::Result<Self>> for TestReport {
     #[inline]
     fn from(value: anyhow::Result<Self>) -> Self {
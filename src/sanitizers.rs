@@ -0,0 +1,138 @@
+//! Reports of AddressSanitizer, UndefinedBehaviorSanitizer and their kin,
+//! for programs built with `-fsanitize=...`.
+
+use bstr::ByteSlice;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// One line per report found in the program's stderr, in order.
+///
+/// Addresses and values change from run to run, so they are left out:
+/// the same bug gives the same line.
+pub fn summarize(stderr: &[u8]) -> Vec<String> {
+    let mut reports: Vec<String> = Vec::new();
+    // the report waiting for its `SUMMARY` line: the tool and the index
+    let mut pending: Option<(String, usize)> = None;
+
+    for line in stderr.lines().map(|line| line.to_str_lossy()) {
+        if let Some(caps) = ERROR.captures(&line) {
+            let kind = caps[2].split(" on ").next().unwrap_or_default();
+
+            pending = Some((caps[1].to_owned(), reports.len()));
+            reports.push(format!("{}: {}", &caps[1], kind));
+        } else if let Some(caps) = RUNTIME_ERROR.captures(&line) {
+            let kind = caps[2].split(": ").next().unwrap_or_default();
+
+            reports.push(format!(
+                "UndefinedBehaviorSanitizer: {} ({})",
+                kind, &caps[1]
+            ));
+        } else if let Some(caps) = SUMMARY.captures(&line) {
+            // has less to say than the `runtime error` lines
+            if &caps[1] == "UndefinedBehaviorSanitizer" {
+                continue;
+            }
+
+            // the summary tells where, the error line only what
+            match pending.take() {
+                Some((tool, index)) => reports[index] = format!("{}: {}", tool, &caps[2]),
+                None => reports.push(format!("{}: {}", &caps[1], &caps[2])),
+            }
+        }
+    }
+
+    let mut unique = Vec::with_capacity(reports.len());
+
+    for report in reports {
+        if !unique.contains(&report) {
+            unique.push(report);
+        }
+    }
+
+    unique
+}
+
+/// `==1234==ERROR: AddressSanitizer: SEGV on unknown address 0x000000000000 ...`
+static ERROR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^==\d+==\s*ERROR: (\w+Sanitizer): (.+)$").unwrap());
+
+/// `main.c:5:13: runtime error: signed integer overflow: 2147483647 + 1 ...`,
+/// maybe after an unfinished line of the program's own.
+static RUNTIME_ERROR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|\s)(\S+:\d+(?::\d+)?): runtime error: (.+?)\s*$").unwrap());
+
+/// `SUMMARY: AddressSanitizer: heap-buffer-overflow /src/main.c:12:5 in main`
+static SUMMARY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^SUMMARY: (\w+Sanitizer): (.+?)\s*$").unwrap());
+
+//===================================================================================//
+//===================================// TESTING //===================================//
+//===================================================================================//
+
+#[cfg(test)]
+mod test_summarize {
+    use super::summarize;
+
+    const ASAN: &str = "\
+=================================================================
+==4242==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000014 at pc 0x55d4 bp 0x7ffd sp 0x7ffc
+WRITE of size 4 at 0x602000000014 thread T0
+    #0 0x55d4 in main /src/main.c:7:10
+SUMMARY: AddressSanitizer: heap-buffer-overflow /src/main.c:7:10 in main
+==4242==ABORTING
+";
+
+    #[test]
+    fn address_sanitizer() {
+        assert_eq!(
+            summarize(ASAN.as_bytes()),
+            ["AddressSanitizer: heap-buffer-overflow /src/main.c:7:10 in main"]
+        );
+    }
+
+    #[test]
+    fn without_summary() {
+        let stderr =
+            "==7==ERROR: AddressSanitizer: SEGV on unknown address 0x000000000000 (pc 0x1)\n";
+
+        assert_eq!(summarize(stderr.as_bytes()), ["AddressSanitizer: SEGV"]);
+    }
+
+    #[test]
+    fn leaks() {
+        let stderr = "\
+==9==ERROR: LeakSanitizer: detected memory leaks
+
+Direct leak of 24 byte(s) in 1 object(s) allocated from:
+SUMMARY: AddressSanitizer: 24 byte(s) leaked in 1 allocation(s).
+";
+
+        assert_eq!(
+            summarize(stderr.as_bytes()),
+            ["LeakSanitizer: 24 byte(s) leaked in 1 allocation(s)."]
+        );
+    }
+
+    #[test]
+    fn undefined_behavior() {
+        let stderr = "\
+Enter N: main.c:5:13: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'
+main.c:5:13: runtime error: signed integer overflow: 2147483646 + 2 cannot be represented in type 'int'
+main.c:9:3: runtime error: load of null pointer of type 'int'
+SUMMARY: UndefinedBehaviorSanitizer: undefined-behavior main.c:5:13 in
+";
+
+        assert_eq!(
+            summarize(stderr.as_bytes()),
+            [
+                "UndefinedBehaviorSanitizer: signed integer overflow (main.c:5:13)",
+                "UndefinedBehaviorSanitizer: load of null pointer of type 'int' (main.c:9:3)",
+            ]
+        );
+    }
+
+    #[test]
+    fn ordinary_errors() {
+        assert!(summarize(b"error: file not found\nSegmentation fault\n").is_empty());
+    }
+}
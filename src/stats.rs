@@ -0,0 +1,429 @@
+use rand::Rng;
+use std::{fmt::Display, time::Duration};
+
+/// Measurements collected from the runs of a testing session.
+#[derive(Debug, Default)]
+pub struct Samples {
+    /// From spawn to exit, for the runs that got that far.
+    pub durations: DurationSamples,
+    /// How long `Output` steps waited for their line.
+    pub output_waits: DurationSamples,
+    /// User and system time together.
+    pub cpu_times: DurationSamples,
+    pub peak_memory: MemorySamples,
+}
+
+/// Durations of one kind, in bounded memory: the count, the mean and the
+/// extremes are exact, the rest comes from a random sample of them.
+#[derive(Debug, Default)]
+pub struct DurationSamples {
+    count: usize,
+    sum: Duration,
+    min: Duration,
+    max: Duration,
+    /// Every duration has the same chance to be kept.
+    reservoir: Vec<Duration>,
+}
+
+impl DurationSamples {
+    pub const RESERVOIR_SIZE: usize = 10_000;
+
+    pub fn push(&mut self, sample: Duration) {
+        if self.count == 0 {
+            (self.min, self.max) = (sample, sample);
+        } else {
+            self.min = self.min.min(sample);
+            self.max = self.max.max(sample);
+        }
+
+        self.count += 1;
+        self.sum = self.sum.saturating_add(sample);
+
+        if self.reservoir.len() < Self::RESERVOIR_SIZE {
+            self.reservoir.push(sample);
+        } else {
+            let index = rand::thread_rng().gen_range(0..self.count);
+
+            if let Some(kept) = self.reservoir.get_mut(index) {
+                *kept = sample;
+            }
+        }
+    }
+}
+
+impl Extend<Duration> for DurationSamples {
+    #[inline]
+    fn extend<I: IntoIterator<Item = Duration>>(&mut self, samples: I) {
+        for sample in samples {
+            self.push(sample);
+        }
+    }
+}
+
+/// Peak memory, in bytes. Only the summary is kept.
+#[derive(Debug, Default)]
+pub struct MemorySamples {
+    count: usize,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl MemorySamples {
+    pub fn push(&mut self, sample: u64) {
+        if self.count == 0 {
+            (self.min, self.max) = (sample, sample);
+        } else {
+            self.min = self.min.min(sample);
+            self.max = self.max.max(sample);
+        }
+
+        self.count += 1;
+        self.sum += sample as u128;
+    }
+}
+
+/// How long the runs took and what they used, over a whole testing session.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunStats {
+    pub runs: Option<DurationStats>,
+    pub output_waits: Option<DurationStats>,
+    pub cpu_time: Option<DurationStats>,
+    pub peak_memory: Option<MemoryStats>,
+}
+
+impl RunStats {
+    #[inline]
+    pub fn new(samples: Samples) -> Self {
+        Self {
+            runs: DurationStats::new(samples.durations),
+            output_waits: DurationStats::new(samples.output_waits),
+            cpu_time: DurationStats::new(samples.cpu_times),
+            peak_memory: MemoryStats::new(samples.peak_memory),
+        }
+    }
+}
+
+impl Display for RunStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(runs) = self.runs.as_ref() {
+            writeln!(f, "Время работы программы:\n{}", runs)?;
+        }
+
+        if let Some(output_waits) = self.output_waits.as_ref() {
+            writeln!(f, "Ожидание вывода:\n{}", output_waits)?;
+        }
+
+        if let Some(cpu_time) = self.cpu_time.as_ref() {
+            writeln!(f, "Время процессора:\n{}", cpu_time)?;
+        }
+
+        if let Some(peak_memory) = self.peak_memory.as_ref() {
+            writeln!(f, "Пиковая память:\n{}", peak_memory)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DurationStats {
+    pub count: usize,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+    /// Equal-width buckets from `min` to `max`, a single one if they are
+    /// equal. Estimated from the sample if there are more durations.
+    pub histogram: Vec<usize>,
+}
+
+impl DurationStats {
+    pub const HISTOGRAM_BUCKETS: usize = 10;
+
+    /// `None` if there are no samples.
+    pub fn new(samples: DurationSamples) -> Option<Self> {
+        let DurationSamples {
+            count,
+            sum,
+            min,
+            max,
+            reservoir: mut sorted,
+        } = samples;
+
+        if count == 0 {
+            return None;
+        }
+
+        sorted.sort_unstable();
+
+        // nearest rank
+        let percentile = |p: usize| sorted[(sorted.len() * p).div_ceil(100).max(1) - 1];
+
+        let buckets = if min < max {
+            Self::HISTOGRAM_BUCKETS
+        } else {
+            1
+        };
+        let mut histogram = vec![0; buckets];
+        let width = (max - min).as_secs_f64() / buckets as f64;
+
+        for sample in sorted.iter() {
+            let bucket = if width > 0.0 {
+                ((*sample - min).as_secs_f64() / width) as usize
+            } else {
+                0
+            };
+
+            // the maximum belongs to the last bucket
+            histogram[bucket.min(buckets - 1)] += 1;
+        }
+
+        if sorted.len() < count {
+            for bucket in histogram.iter_mut() {
+                *bucket = (*bucket as f64 * count as f64 / sorted.len() as f64).round() as usize;
+            }
+        }
+
+        Some(Self {
+            count,
+            min,
+            // `count` may not fit in `u32`
+            mean: Duration::from_nanos((sum.as_nanos() / count as u128) as u64),
+            p50: percentile(50),
+            p95: percentile(95),
+            max,
+            histogram,
+        })
+    }
+
+    /// The lower and upper bounds of a histogram bucket.
+    pub fn bucket_bounds(&self, bucket: usize) -> (Duration, Duration) {
+        let width = (self.max - self.min) / self.histogram.len() as u32;
+
+        (
+            self.min + width * bucket as u32,
+            self.min + width * (bucket as u32 + 1),
+        )
+    }
+}
+
+impl Display for DurationStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "замеров: {}, мин.: {}, сред.: {}, p50: {}, p95: {}, макс.: {}",
+            self.count,
+            format_duration(self.min),
+            format_duration(self.mean),
+            format_duration(self.p50),
+            format_duration(self.p95),
+            format_duration(self.max),
+        )?;
+
+        let largest = self.histogram.iter().copied().max().unwrap_or(0).max(1);
+
+        for (bucket, &count) in self.histogram.iter().enumerate() {
+            let (from, to) = self.bucket_bounds(bucket);
+            let bar = "#".repeat(count * HISTOGRAM_WIDTH / largest);
+
+            writeln!(
+                f,
+                "{:>10} - {:<10} | {:<width$} {}",
+                format_duration(from),
+                format_duration(to),
+                bar,
+                count,
+                width = HISTOGRAM_WIDTH
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+const HISTOGRAM_WIDTH: usize = 40;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub count: usize,
+    /// All in bytes.
+    pub min: u64,
+    pub mean: u64,
+    pub max: u64,
+}
+
+impl MemoryStats {
+    /// `None` if there are no samples.
+    pub fn new(samples: MemorySamples) -> Option<Self> {
+        if samples.count == 0 {
+            return None;
+        }
+
+        Some(Self {
+            count: samples.count,
+            min: samples.min,
+            mean: (samples.sum / samples.count as u128) as u64,
+            max: samples.max,
+        })
+    }
+}
+
+impl Display for MemoryStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "замеров: {}, мин.: {}, сред.: {}, макс.: {}",
+            self.count,
+            format_bytes(self.min),
+            format_bytes(self.mean),
+            format_bytes(self.max),
+        )
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{:.1} мс", duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2} с", duration.as_secs_f64())
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const KIB: u64 = 1024;
+    const MIB: u64 = KIB * 1024;
+
+    if bytes < MIB {
+        format!("{:.1} КиБ", bytes as f64 / KIB as f64)
+    } else {
+        format!("{:.1} МиБ", bytes as f64 / MIB as f64)
+    }
+}
+
+//===================================================================================//
+//===================================// TESTING //===================================//
+//===================================================================================//
+
+#[cfg(test)]
+mod test_duration_stats {
+    use super::{DurationSamples, DurationStats, MemorySamples, MemoryStats};
+    use std::time::Duration;
+
+    fn millis(values: impl IntoIterator<Item = u64>) -> DurationSamples {
+        let mut samples = DurationSamples::default();
+        samples.extend(values.into_iter().map(Duration::from_millis));
+
+        samples
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(DurationStats::new(DurationSamples::default()), None);
+    }
+
+    #[test]
+    fn percentiles() {
+        let stats = DurationStats::new(millis((1..=100).rev())).unwrap();
+
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(stats.mean, Duration::from_micros(50_500));
+        assert_eq!(stats.p50, Duration::from_millis(50));
+        assert_eq!(stats.p95, Duration::from_millis(95));
+    }
+
+    #[test]
+    fn single_sample() {
+        let stats = DurationStats::new(millis([7])).unwrap();
+
+        assert_eq!(stats.p50, Duration::from_millis(7));
+        assert_eq!(stats.p95, Duration::from_millis(7));
+        assert_eq!(stats.histogram, [1]);
+    }
+
+    #[test]
+    fn equal_samples() {
+        let stats = DurationStats::new(millis([5; 4])).unwrap();
+
+        assert_eq!(stats.histogram, [4]);
+        assert_eq!(stats.to_string().lines().count(), 2);
+    }
+
+    #[test]
+    fn many_samples() {
+        let count = 10 * DurationSamples::RESERVOIR_SIZE as u64;
+        let samples = millis((0..count).map(|i| i % 1000));
+
+        assert_eq!(samples.reservoir.len(), DurationSamples::RESERVOIR_SIZE);
+
+        let stats = DurationStats::new(samples).unwrap();
+
+        assert_eq!(stats.count, count as usize);
+        assert_eq!(stats.min, Duration::ZERO);
+        assert_eq!(stats.max, Duration::from_millis(999));
+        assert_eq!(stats.mean, Duration::from_micros(499_500));
+        // from the sample => close enough
+        assert!(stats.p50.abs_diff(Duration::from_millis(500)) < Duration::from_millis(50));
+        assert!(
+            stats
+                .histogram
+                .iter()
+                .sum::<usize>()
+                .abs_diff(count as usize)
+                < 10
+        );
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn count_beyond_u32() {
+        let count = 3 << 32;
+        let samples = DurationSamples {
+            count,
+            sum: Duration::from_micros(count as u64),
+            min: Duration::from_micros(1),
+            max: Duration::from_micros(1),
+            reservoir: vec![Duration::from_micros(1)],
+        };
+
+        let stats = DurationStats::new(samples).unwrap();
+
+        assert_eq!(stats.mean, Duration::from_micros(1));
+        assert_eq!(stats.histogram, [count]);
+    }
+
+    #[test]
+    fn histogram() {
+        let stats = DurationStats::new(millis([0, 1, 9, 10, 50, 99, 100])).unwrap();
+
+        assert_eq!(stats.histogram, [3, 1, 0, 0, 0, 1, 0, 0, 0, 2]);
+        assert_eq!(
+            stats.bucket_bounds(9),
+            (Duration::from_millis(90), Duration::from_millis(100))
+        );
+    }
+
+    #[test]
+    fn memory() {
+        assert_eq!(MemoryStats::new(MemorySamples::default()), None);
+
+        let mut samples = MemorySamples::default();
+
+        for sample in [3 << 20, 1 << 20, 2 << 20] {
+            samples.push(sample);
+        }
+
+        assert_eq!(
+            MemoryStats::new(samples),
+            Some(MemoryStats {
+                count: 3,
+                min: 1 << 20,
+                mean: 2 << 20,
+                max: 3 << 20,
+            })
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! A pseudo-terminal for the program's stdin and stdout.
+//!
+//! The C library buffers the output fully when it goes to a pipe, so prompts
+//! only show up once the program exits. On a terminal the output is flushed
+//! line by line and whenever the program reads its input, just like for a
+//! human user.
+
+use std::{
+    fs::File,
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+/// Returns the master side (for us) and the slave side (for the program).
+///
+/// The terminal neither echoes the input nor turns `\n` into `\r\n`, so the
+/// program's output reads the same as through a pipe. The input stays line
+/// by line, the system cuts lines longer than 4095 bytes, and `VEOF` (0x04)
+/// still ends a line early.
+pub fn open() -> io::Result<(File, OwnedFd)> {
+    let master = unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        OwnedFd::from_raw_fd(fd)
+    };
+
+    let slave = unsafe {
+        if libc::grantpt(master.as_raw_fd()) != 0 || libc::unlockpt(master.as_raw_fd()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut path = [0; 64];
+
+        // the plain `ptsname` isn't thread-safe
+        let error = libc::ptsname_r(master.as_raw_fd(), path.as_mut_ptr(), path.len());
+        if error != 0 {
+            return Err(io::Error::from_raw_os_error(error));
+        }
+
+        let fd = libc::open(
+            path.as_ptr(),
+            libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC,
+        );
+
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        OwnedFd::from_raw_fd(fd)
+    };
+
+    configure(&slave)?;
+
+    Ok((File::from(master), slave))
+}
+
+fn configure(slave: &OwnedFd) -> io::Result<()> {
+    // plain data => zeroes are a valid value
+    let mut termios: libc::termios = unsafe { mem::zeroed() };
+
+    if unsafe { libc::tcgetattr(slave.as_raw_fd(), &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // generated input may hold other control bytes => no signals, flow control
+    // or editing keys, only `VEOF` is kept to end the input
+    termios.c_lflag &= !(libc::ECHO | libc::ECHONL | libc::ISIG | libc::IEXTEN);
+    termios.c_iflag &= !(libc::ICRNL | libc::INLCR | libc::IGNCR | libc::IXON | libc::IXOFF);
+    termios.c_oflag &= !libc::ONLCR;
+
+    for key in [libc::VERASE, libc::VKILL, libc::VEOL, libc::VEOL2] {
+        termios.c_cc[key] = libc::_POSIX_VDISABLE;
+    }
+
+    if unsafe { libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
@@ -1,25 +1,35 @@
 use crate::{
-    communicator::{CommReport, Communicator},
-    gui::{ContentType, RuleData, RuleType},
-    rules::{IntRanges, PlainText, RegExpr, Rule},
-    run_manager::{SharedRunnerState, TestReport, TestingData},
+    communicator::{CommReport, Communicator, History, ReadResult, ResourceUsage, Stream},
+    exit_status::{self, ExitExpectation},
+    rule_data::{ArgData, ContentType, RuleData, RuleType},
+    rules::{Choices, IntRanges, PlainText, RegExpr, Rule},
+    run_manager::{
+        FailureGroup, FailureKind, FailureReport, FailureSignature, LaunchSettings, Minimized,
+        SharedRunnerState, Summary, TestReport, TestingData,
+    },
+    sanitizers,
+    stats::{self, RunStats, Samples},
     DATE_FORMAT,
 };
+use bstr::{BString, ByteSlice};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::{
     fmt::Debug,
-    fs,
-    process::{Command, Stdio},
+    fs, mem,
+    path::Path,
+    process::{Command, ExitStatus},
     sync::{
-        atomic::Ordering,
+        atomic::{AtomicU32, Ordering},
         mpsc::{Receiver, SyncSender},
-        Arc,
+        Arc, Mutex,
     },
     thread,
+    time::{Duration, Instant},
 };
 
 #[derive(Debug)]
 pub struct Runner {
-    work_state: Arc<SharedRunnerState>,
+    tester: Tester,
     work_receiver: Receiver<TestingData>,
     result_sender: SyncSender<TestReport>,
 }
@@ -32,7 +42,7 @@ impl Runner {
         result_sender: SyncSender<TestReport>,
     ) -> Self {
         Self {
-            work_state,
+            tester: Tester::new(work_state),
             work_receiver,
             result_sender,
         }
@@ -41,98 +51,927 @@ impl Runner {
     pub fn start(mut self) {
         thread::spawn(move || {
             while let Ok(testing_data) = self.work_receiver.recv() {
-                let result = self.run_tests(testing_data);
+                let result = self.tester.run(testing_data);
 
-                if self.result_sender.send(result.into()).is_err() {
+                if self.result_sender.send(result).is_err() {
                     // result channel disconnected => main thread died
                     break;
                 }
 
-                self.work_state.reset();
+                self.tester.work_state.reset();
             }
 
             // work channel disconnected => main thread died
         });
     }
+}
+
+/// Runs the tests and blocks until they are done.
+///
+/// `Runner` uses it on its own thread; `work_state` shows the progress and
+/// lets another thread stop the testing.
+#[derive(Debug)]
+pub struct Tester {
+    work_state: Arc<SharedRunnerState>,
+}
+
+impl Tester {
+    #[inline]
+    pub fn new(work_state: Arc<SharedRunnerState>) -> Self {
+        Self { work_state }
+    }
+
+    #[inline]
+    pub fn run(&mut self, testing_data: TestingData) -> TestReport {
+        self.run_tests(testing_data).into()
+    }
 
     fn run_tests(&mut self, testing_data: TestingData) -> anyhow::Result<TestReport> {
-        let mut command = Command::new(testing_data.program_path);
-        command.stdin(Stdio::piped()).stdout(Stdio::piped());
+        let scenario = Scenario::process(&testing_data.rules, &testing_data.launch.args)?;
+
+        if let Some(wrapper) = testing_data.launch.wrapper.as_ref() {
+            if wrapper.command.is_empty() {
+                anyhow::bail!("Команда-обёртка не указана");
+            }
+        }
 
-        let ops = Operation::process(&testing_data.rules)?;
+        let required_tests = if testing_data.replay_seed.is_some() {
+            1
+        } else {
+            testing_data.successes_required
+        };
 
         self.work_state
             .required_tests
-            .store(testing_data.successes_required, Ordering::Release);
+            .store(required_tests, Ordering::Release);
 
-        let mut success_histories = Vec::new();
+        let master_seed = testing_data
+            .master_seed
+            .unwrap_or_else(|| rand::thread_rng().gen());
 
-        while self.work_state.solved_tests.fetch_add(1, Ordering::AcqRel)
-            < self.work_state.required_tests.load(Ordering::Acquire)
-        {
-            let result = self.run_single(&mut command, &ops, &mut success_histories)?;
+        let start_time = Instant::now();
+        *self.work_state.start_time.lock().unwrap() = Some(start_time);
 
-            if !matches!(result, TestReport::Success) {
-                return Ok(result);
+        let deadline = match testing_data.replay_seed {
+            Some(_) => None,
+            None => testing_data.time_budget.map(|budget| start_time + budget),
+        };
+
+        let pool = Pool {
+            testing_data: &testing_data,
+            scenario: &scenario,
+            master_seed,
+            deadline,
+            failed_index: AtomicU32::new(u32::MAX),
+            failures: Mutex::new(Vec::new()),
+            error: Mutex::new(None),
+            success_histories: Mutex::new(Vec::new()),
+            samples: Mutex::default(),
+        };
+
+        thread::scope(|scope| {
+            for _i in 0..testing_data.workers.max(1) {
+                scope.spawn(|| self.run_worker(&pool));
             }
+        });
+
+        if let Some(error) = pool.error.into_inner().unwrap() {
+            return Err(error);
         }
 
+        let mut success_histories = pool.success_histories.into_inner().unwrap();
+        success_histories.sort_by_key(|(index, _)| *index);
+
         save_to_file(
             "Успехи",
-            &success_histories.join("\n#====================#\n"),
+            &success_histories
+                .into_iter()
+                .map(|(_, history)| history)
+                .collect::<Vec<_>>()
+                .join(REPORT_SEPARATOR),
         );
 
-        Ok(TestReport::Success)
+        let run_stats = RunStats::new(pool.samples.into_inner().unwrap());
+
+        let mut failures = pool.failures.into_inner().unwrap();
+        failures.sort_by_key(|failed| failed.index);
+
+        if testing_data.continue_on_failure {
+            let mut groups = Vec::new();
+
+            for (count, failed) in group_failures(failures) {
+                let representative =
+                    self.minimize_failure(&testing_data, &scenario, deadline, failed)?;
+
+                groups.push(FailureGroup {
+                    count,
+                    representative,
+                });
+            }
+
+            save_failures(
+                groups
+                    .iter()
+                    .map(|group| {
+                        format!(
+                            "#== Повторений: {} ({}) ==#\n{}",
+                            group.count,
+                            group.representative.signature,
+                            failure_text(&group.representative)
+                        )
+                    })
+                    .collect(),
+            );
+
+            return Ok(TestReport::Summary(Summary {
+                passed: self.work_state.passed.load(Ordering::Acquire),
+                failed: self.work_state.failed.load(Ordering::Acquire),
+                crashed: self.work_state.crashed.load(Ordering::Acquire),
+                timed_out: self.work_state.timed_out.load(Ordering::Acquire),
+                groups,
+                run_stats,
+            }));
+        }
+
+        match failures.into_iter().next() {
+            Some(failed) => {
+                let report = self.minimize_failure(&testing_data, &scenario, deadline, failed)?;
+                save_failures(vec![failure_text(&report)]);
+
+                Ok(TestReport::Failure(report, run_stats))
+            }
+            None => Ok(TestReport::Success(run_stats)),
+        }
     }
 
-    fn run_single(
+    /// Timeouts aren't minimized: every attempt would take the whole timeout.
+    fn minimize_failure(
         &mut self,
-        command: &mut Command,
-        operations: &[Operation],
-        success_histories: &mut Vec<String>,
-    ) -> anyhow::Result<TestReport> {
-        let mut comm = Communicator::new(command)?;
-
-        for op in operations.iter() {
-            match op.exec(&mut comm)? {
-                OpReport::Success => {}
-                OpReport::Failure { error_message } => {
-                    save_to_file("Ошибки", &format!("{}\n{}", &comm.history, &error_message));
-
-                    return Ok(TestReport::Failure {
-                        history: comm.history,
-                        error_message,
-                    });
+        testing_data: &TestingData,
+        scenario: &Scenario,
+        deadline: Option<Instant>,
+        failed: FailedRun,
+    ) -> anyhow::Result<FailureReport> {
+        let failure = failed.failure();
+
+        if failure.kind == FailureKind::TimedOut {
+            return Ok(failed.into_report(None));
+        }
+
+        let picks = failed.outcome.picks.clone();
+        let minimized = self.minimize(
+            testing_data,
+            scenario,
+            failed.seed,
+            &failure.signature,
+            picks,
+            deadline,
+        )?;
+
+        Ok(failed.into_report(minimized))
+    }
+
+    /// Takes runs one by one until there are none left.
+    ///
+    /// Unless all runs are required, runs that come after a failed one aren't
+    /// started and only the earliest failure is reported, so the result
+    /// doesn't depend on the number of workers.
+    ///
+    /// Runs that are going when the time is up are finished.
+    fn run_worker(&self, pool: &Pool) {
+        loop {
+            if pool
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                break;
+            }
+
+            let index = self.work_state.solved_tests.fetch_add(1, Ordering::AcqRel);
+
+            if index >= self.work_state.required_tests.load(Ordering::Acquire)
+                || index > pool.failed_index.load(Ordering::Acquire)
+            {
+                break;
+            }
+
+            let seed = match pool.testing_data.replay_seed {
+                Some(seed) => seed,
+                None => run_seed(pool.master_seed, index),
+            };
+
+            let outcome = self
+                .check_cancelled()
+                .and_then(|()| self.run_single(pool.testing_data, pool.scenario, seed, None));
+
+            match outcome {
+                Ok(mut outcome) => {
+                    let mut samples = pool.samples.lock().unwrap();
+
+                    samples.durations.extend(outcome.duration);
+                    samples.output_waits.extend(outcome.output_waits.drain(..));
+
+                    if let Some(usage) = outcome.usage {
+                        samples.cpu_times.push(usage.cpu_time());
+                        samples.peak_memory.push(usage.peak_memory);
+                    }
+
+                    drop(samples);
+
+                    match outcome.failure {
+                        None => {
+                            self.work_state.passed.fetch_add(1, Ordering::AcqRel);
+
+                            let history = outcome.history.to_string();
+                            pool.success_histories
+                                .lock()
+                                .unwrap()
+                                .push((index, history));
+                        }
+                        Some(RunFailure { kind, .. }) => {
+                            self.work_state.counter(kind).fetch_add(1, Ordering::AcqRel);
+
+                            if !pool.testing_data.continue_on_failure {
+                                pool.failed_index.fetch_min(index, Ordering::AcqRel);
+                            }
+
+                            pool.failures.lock().unwrap().push(FailedRun {
+                                index,
+                                seed,
+                                outcome,
+                            });
+                        }
+                    }
+                }
+                Err(error) => {
+                    // no run is started after this one
+                    pool.failed_index.store(0, Ordering::Release);
+                    pool.error.lock().unwrap().get_or_insert(error);
+
+                    break;
                 }
             }
         }
+    }
+
+    #[inline]
+    fn check_cancelled(&self) -> anyhow::Result<()> {
+        if self.work_state.cancel.is_cancelled() {
+            anyhow::bail!("Тестирование остановлено");
+        }
+
+        Ok(())
+    }
+
+    /// Runs the program once. `Input` steps repeat the given decisions
+    /// if there are any, and make random ones otherwise.
+    fn run_single(
+        &self,
+        testing_data: &TestingData,
+        scenario: &Scenario,
+        seed: u64,
+        replay: Option<&[Vec<u128>]>,
+    ) -> anyhow::Result<RunOutcome> {
+        let rules = &testing_data.rules;
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let args = scenario
+            .arguments
+            .iter()
+            .map(|rule| rule.generate(&mut rng))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut command = build_command(&testing_data.program_path, &testing_data.launch, &args);
+        let mut comm = Communicator::new(
+            &mut command,
+            testing_data.comm_settings,
+            self.work_state.cancel.clone(),
+        )?;
+
+        let mut picks = Vec::new();
+        let mut output_waits = Vec::new();
+
+        for (index, (op, rule)) in scenario.operations.iter().zip(rules).enumerate() {
+            self.check_cancelled()?;
+
+            let mut choices = match replay {
+                Some(replay) => Choices::replay(replay.get(picks.len()).map_or(&[], Vec::as_slice)),
+                None => Choices::random(&mut rng),
+            };
+
+            let start_time = Instant::now();
+            let report = op.exec(&mut comm, &mut choices)?;
+
+            match op {
+                Operation::Input(_) => picks.push(choices.into_picks()),
+                Operation::Output(_)
+                | Operation::Prompt(_)
+                | Operation::ErrOutput(_)
+                | Operation::ErrPrompt(_) => output_waits.push(start_time.elapsed()),
+                Operation::CloseInput | Operation::SendSignal(_) => {}
+            }
+
+            if let OpReport::Failure { error_message } = report {
+                self.check_cancelled()?;
+
+                // the program was killed over the timeout or its output =>
+                // its exit status means nothing
+                let (kind, status) = match (comm.timed_out(), comm.output_limit()) {
+                    (Some(_), _) => (FailureKind::TimedOut, None),
+                    (None, Some(_)) => (FailureKind::Failed, None),
+                    (None, None) => {
+                        let status = comm.exit_status();
+
+                        (scenario.failure_kind(status), status)
+                    }
+                };
+
+                let limit = status.and_then(|status| {
+                    testing_data
+                        .launch
+                        .effective_limits()
+                        .hit(status, comm.stderr())
+                });
+
+                let mut error_message = error_message;
+                if let Some(status) = status.filter(|status| !status.success()) {
+                    error_message +=
+                        &format!("\nСтатус завершения: {}", exit_status::describe(status));
+                }
+
+                return Ok(RunOutcome {
+                    failure: Some(
+                        RunFailure::new(kind, Some((index, rule)), &error_message, status)
+                            .with_cause(limit)
+                            .with_cause(sanitizer_reports(comm.stderr()))
+                            .with_cause(
+                                status.and_then(|status| {
+                                    wrapper_category(&testing_data.launch, status)
+                                }),
+                            ),
+                    ),
+                    history: mem::take(&mut comm.history),
+                    picks,
+                    duration: None,
+                    usage: comm.usage(),
+                    output_waits,
+                });
+            }
+        }
 
         let report = comm.finish()?;
 
-        match report {
-            CommReport::Success(history) => {
-                success_histories.push(history.to_string());
-                Ok(TestReport::Success)
+        // a killed program fails in all sorts of ways => don't report them
+        self.check_cancelled()?;
+
+        let (failure, history, duration, usage) = match report {
+            CommReport::Exited {
+                history,
+                status,
+                duration,
+                usage,
+                unread_stdout,
+                stderr,
+            } => {
+                let kind = scenario.failure_kind(Some(status));
+
+                let failure = match scenario.exit.as_ref().map(|exit| exit.validate(status)) {
+                    Some(OpReport::Failure { error_message }) => {
+                        // the exit check always follows the last operation
+                        let index = scenario.operations.len();
+
+                        Some(RunFailure::new(
+                            kind,
+                            Some((index, &rules[index])),
+                            &error_message,
+                            Some(status),
+                        ))
+                    }
+                    None if !status.success() => Some(RunFailure::new(
+                        kind,
+                        None,
+                        &format!(
+                            "Программа не была успешно завершена: {}\n{}",
+                            exit_status::describe(status),
+                            stderr
+                        ),
+                        Some(status),
+                    )),
+                    _ if unread_stdout => Some(RunFailure::new(
+                        FailureKind::Failed,
+                        None,
+                        "Программа вывела лишние данные",
+                        Some(status),
+                    )),
+                    // the undefined behavior sanitizer doesn't stop the program by default
+                    _ if !sanitizers::summarize(&stderr).is_empty() => Some(RunFailure::new(
+                        FailureKind::Failed,
+                        None,
+                        &format!("Санитайзер обнаружил ошибки в программе:\n{}", stderr),
+                        Some(status),
+                    )),
+                    _ => check_limits(testing_data, duration, usage).map(|error_message| {
+                        RunFailure::new(FailureKind::Failed, None, &error_message, Some(status))
+                    }),
+                };
+
+                let limit = testing_data.launch.effective_limits().hit(status, &stderr);
+                let failure = failure.map(|failure| {
+                    failure
+                        .with_cause(limit)
+                        .with_cause(sanitizer_reports(&stderr))
+                        .with_cause(wrapper_category(&testing_data.launch, status))
+                });
+
+                (failure, history, Some(duration), usage)
             }
-            CommReport::NonEmptyStdout(history) => {
-                let error_message = "Программа вывела лишние данные";
+            CommReport::TimedOut(history, kind) => {
+                let error_message = format!("{} (ожидание завершения программы)", kind);
 
-                save_to_file("Ошибки", &format!("{}\n{}", &history, &error_message));
-                Ok(TestReport::Failure {
-                    history,
-                    error_message: error_message.to_owned(),
-                })
+                let failure = RunFailure::new(FailureKind::TimedOut, None, &error_message, None);
+
+                (Some(failure), history, None, None)
             }
-            CommReport::ProgramError(history, stderr) => {
-                let error_message = format!("Программа не была успешно завершена:\n{}", stderr);
+            CommReport::OutputLimit(history, limit) => {
+                let failure = RunFailure::new(FailureKind::Failed, None, &limit.to_string(), None);
 
-                Ok(TestReport::Failure {
-                    history,
-                    error_message,
-                })
+                (Some(failure), history, None, None)
+            }
+        };
+
+        Ok(RunOutcome {
+            failure,
+            history,
+            picks,
+            duration,
+            usage,
+            output_waits,
+        })
+    }
+
+    /// Looks for simpler inputs that still make the program fail.
+    ///
+    /// The decisions behind every `Input` step are deleted and lowered one
+    /// by one, keeping any change that fails again. Returns `None` if
+    /// nothing simpler was found.
+    ///
+    /// Stops with the best inputs so far once it runs out of runs or time,
+    /// or at `deadline`, the end of the testing session.
+    fn minimize(
+        &mut self,
+        testing_data: &TestingData,
+        scenario: &Scenario,
+        seed: u64,
+        signature: &FailureSignature,
+        picks: Vec<Vec<u128>>,
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<Option<Minimized>> {
+        let time_is_up = Instant::now() + MAX_SHRINK_TIME;
+
+        let mut shrinker = Shrinker {
+            signature: signature.clone(),
+            picks,
+            minimized: None,
+            runs_left: MAX_SHRINK_RUNS,
+            deadline: deadline.map_or(time_is_up, |deadline| deadline.min(time_is_up)),
+        };
+
+        loop {
+            let before = shrinker.picks.clone();
+
+            for step in 0..shrinker.picks.len() {
+                let mut pos = 0;
+
+                while pos < shrinker.picks.get(step).map_or(0, Vec::len) {
+                    for width in [8, 4, 2, 1] {
+                        let current = &shrinker.picks[step];
+
+                        if pos + width <= current.len() {
+                            let mut candidate = current.clone();
+                            candidate.drain(pos..pos + width);
+
+                            self.try_shrink(
+                                testing_data,
+                                scenario,
+                                seed,
+                                &mut shrinker,
+                                step,
+                                candidate,
+                            )?;
+                        }
+                    }
+
+                    self.lower_pick(testing_data, scenario, seed, &mut shrinker, step, pos)?;
+
+                    pos += 1;
+                }
+            }
+
+            if shrinker.picks == before || shrinker.is_exhausted() {
+                break;
             }
         }
+
+        Ok(shrinker.minimized)
+    }
+
+    /// Finds the smallest value of a single decision that still fails,
+    /// assuming that lower values are more likely to pass.
+    fn lower_pick(
+        &mut self,
+        testing_data: &TestingData,
+        scenario: &Scenario,
+        seed: u64,
+        shrinker: &mut Shrinker,
+        step: usize,
+        pos: usize,
+    ) -> anyhow::Result<()> {
+        let with_pick = |shrinker: &Shrinker, value: u128| {
+            let mut candidate = shrinker.picks[step].clone();
+            candidate[pos] = value;
+            candidate
+        };
+
+        let Some(&current) = shrinker.picks[step].get(pos) else {
+            return Ok(());
+        };
+
+        if current == 0 {
+            return Ok(());
+        }
+
+        let candidate = with_pick(shrinker, 0);
+        if self.try_shrink(testing_data, scenario, seed, shrinker, step, candidate)? {
+            return Ok(());
+        }
+
+        let (mut passes, mut fails) = (0, current);
+
+        while fails - passes > 1 && !shrinker.is_exhausted() {
+            let middle = passes + (fails - passes) / 2;
+
+            let candidate = with_pick(shrinker, middle);
+            if self.try_shrink(testing_data, scenario, seed, shrinker, step, candidate)? {
+                fails = middle;
+
+                if shrinker.picks[step].get(pos) != Some(&middle) {
+                    // the decisions got rearranged => the search is meaningless now
+                    break;
+                }
+            } else {
+                passes = middle;
+            }
+        }
+
+        Ok(())
     }
+
+    /// Runs the program with `candidate` as the decisions of `Input` step
+    /// number `step` and keeps them if the program fails the same way again.
+    fn try_shrink(
+        &mut self,
+        testing_data: &TestingData,
+        scenario: &Scenario,
+        seed: u64,
+        shrinker: &mut Shrinker,
+        step: usize,
+        candidate: Vec<u128>,
+    ) -> anyhow::Result<bool> {
+        if shrinker.is_exhausted() {
+            return Ok(false);
+        }
+        shrinker.runs_left -= 1;
+
+        let mut replay = shrinker.picks.clone();
+        replay[step] = candidate;
+
+        let outcome = self.run_single(testing_data, scenario, seed, Some(&replay))?;
+
+        match outcome.failure {
+            // a different failure is most likely a different bug
+            Some(failure)
+                if failure.signature == shrinker.signature
+                    && is_simpler(&outcome.picks, &shrinker.picks) =>
+            {
+                shrinker.picks = outcome.picks;
+                shrinker.minimized = Some(Minimized {
+                    history: outcome.history,
+                    error_message: failure.error_message,
+                });
+
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+/// The runs shared between the workers of a single testing session.
+struct Pool<'a> {
+    testing_data: &'a TestingData,
+    scenario: &'a Scenario,
+    master_seed: u64,
+    /// No run is started after this moment.
+    deadline: Option<Instant>,
+    /// No run after this one is started.
+    failed_index: AtomicU32,
+    failures: Mutex<Vec<FailedRun>>,
+    error: Mutex<Option<anyhow::Error>>,
+    success_histories: Mutex<Vec<(u32, String)>>,
+    samples: Mutex<Samples>,
+}
+
+#[derive(Debug)]
+struct FailedRun {
+    index: u32,
+    seed: u64,
+    outcome: RunOutcome,
+}
+
+impl FailedRun {
+    #[inline]
+    fn failure(&self) -> &RunFailure {
+        self.outcome
+            .failure
+            .as_ref()
+            .expect("only failed runs are kept")
+    }
+
+    fn into_report(self, minimized: Option<Minimized>) -> FailureReport {
+        let failure = self.outcome.failure.expect("only failed runs are kept");
+
+        FailureReport {
+            history: self.outcome.history,
+            error_message: failure.error_message,
+            seed: self.seed,
+            kind: failure.kind,
+            signature: failure.signature,
+            usage: self.outcome.usage,
+            minimized,
+        }
+    }
+}
+
+/// Groups the failures by signature, the most common ones first.
+///
+/// Every group keeps the run with the simplest inputs, the earliest one
+/// among equals.
+fn group_failures(failures: Vec<FailedRun>) -> Vec<(u32, FailedRun)> {
+    let mut groups: Vec<(u32, FailedRun)> = Vec::new();
+
+    for failed in failures {
+        let group = groups
+            .iter_mut()
+            .find(|(_, other)| other.failure().signature == failed.failure().signature);
+
+        match group {
+            Some((count, representative)) => {
+                *count += 1;
+
+                if is_simpler(&failed.outcome.picks, &representative.outcome.picks) {
+                    *representative = failed;
+                }
+            }
+            None => groups.push((1, failed)),
+        }
+    }
+
+    // stable => equally common groups stay in the order of the runs
+    groups.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    groups
+}
+
+/// Keeps the program runs spent on minimizing a single failure in check.
+const MAX_SHRINK_RUNS: u32 = 1000;
+/// The same for the time: a run may take up to the whole read timeout.
+const MAX_SHRINK_TIME: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct RunOutcome {
+    /// `None` if the run passed.
+    failure: Option<RunFailure>,
+    history: History,
+    /// The decisions behind every `Input` step that was reached.
+    picks: Vec<Vec<u128>>,
+    /// From spawn to exit, `None` if the run was cut short.
+    duration: Option<Duration>,
+    /// `None` if the program wasn't reaped or it can't be measured.
+    usage: Option<ResourceUsage>,
+    /// How long every step that checks the output waited for it.
+    output_waits: Vec<Duration>,
+}
+
+#[derive(Debug)]
+struct RunFailure {
+    kind: FailureKind,
+    signature: FailureSignature,
+    error_message: String,
+}
+
+impl RunFailure {
+    /// `error_message` comes from the failed step itself, if there is one.
+    fn new(
+        kind: FailureKind,
+        step: Option<(usize, &RuleData)>,
+        error_message: &str,
+        status: Option<ExitStatus>,
+    ) -> Self {
+        let signature = FailureSignature {
+            step: step.map(|(index, _)| index),
+            rule_name: step.map(|(_, rule)| rule.name.clone()),
+            message_kind: error_message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_end_matches(':')
+                .to_owned(),
+            exit: status.map(exit_status::describe),
+        };
+
+        let error_message = match step {
+            Some((index, rule)) => step_failure(index, rule, error_message),
+            None => error_message.to_owned(),
+        };
+
+        Self {
+            kind,
+            signature,
+            error_message,
+        }
+    }
+
+    /// Puts what made the program fail (a resource limit it ran into, the
+    /// reports of a sanitizer) first, so that such failures are grouped by it.
+    fn with_cause(mut self, cause: Option<String>) -> Self {
+        if let Some(cause) = cause {
+            self.error_message = format!("{}\n{}", cause, self.error_message);
+            self.signature.message_kind = cause.lines().next().unwrap_or_default().to_owned();
+        }
+
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Shrinker {
+    /// Candidates must fail the same way as the original run.
+    signature: FailureSignature,
+    picks: Vec<Vec<u128>>,
+    minimized: Option<Minimized>,
+    runs_left: u32,
+    /// No run is started after this moment.
+    deadline: Instant,
+}
+
+impl Shrinker {
+    #[inline]
+    fn is_exhausted(&self) -> bool {
+        self.runs_left == 0 || Instant::now() >= self.deadline
+    }
+}
+
+/// Fewer decisions are simpler, then lower ones.
+fn is_simpler(picks: &[Vec<u128>], than: &[Vec<u128>]) -> bool {
+    let len = |picks: &[Vec<u128>]| picks.iter().map(Vec::len).sum::<usize>();
+
+    len(picks)
+        .cmp(&len(than))
+        .then_with(|| picks.iter().flatten().cmp(than.iter().flatten()))
+        .is_lt()
+}
+
+fn build_command(program_path: &Path, launch: &LaunchSettings, args: &[BString]) -> Command {
+    let wrapper = launch
+        .wrapper
+        .as_ref()
+        .and_then(|wrapper| wrapper.command.split_first());
+
+    let mut command = match wrapper {
+        Some((wrapper, wrapper_args)) => {
+            let mut command = Command::new(wrapper);
+            command.args(wrapper_args).arg(program_path);
+            command
+        }
+        None => Command::new(program_path),
+    };
+
+    command.args(args.iter().map(|arg| arg.to_os_str_lossy()));
+
+    if launch.clear_env {
+        command.env_clear();
+    }
+
+    command.envs(launch.env.iter().map(|(key, value)| (key, value)));
+
+    if let Some(dir) = launch.working_dir.as_ref() {
+        command.current_dir(dir);
+    }
+
+    launch.effective_limits().apply(&mut command);
+
+    command
+}
+
+#[inline]
+fn wrapper_category(launch: &LaunchSettings, status: ExitStatus) -> Option<String> {
+    launch
+        .wrapper
+        .as_ref()
+        .and_then(|wrapper| wrapper.category(status))
+        .map(str::to_owned)
+}
+
+#[inline]
+fn sanitizer_reports(stderr: &[u8]) -> Option<String> {
+    let reports = sanitizers::summarize(stderr);
+
+    (!reports.is_empty()).then(|| reports.join("\n"))
+}
+
+/// The error message if the run went over any of the limits.
+fn check_limits(
+    testing_data: &TestingData,
+    duration: Duration,
+    usage: Option<ResourceUsage>,
+) -> Option<String> {
+    // the first line is the same for every run that goes over the limit
+    if let Some(max) = testing_data.max_run_duration.filter(|&max| duration > max) {
+        return Some(format!(
+            "Программа работала слишком долго\nВремя работы: {}, допустимо не более {}",
+            stats::format_duration(duration),
+            stats::format_duration(max)
+        ));
+    }
+
+    let usage = usage?;
+
+    if let Some(max) = testing_data
+        .max_peak_memory
+        .filter(|&max| usage.peak_memory > max)
+    {
+        return Some(format!(
+            "Программа использовала слишком много памяти\nПиковая память: {}, допустимо не более {}",
+            stats::format_bytes(usage.peak_memory),
+            stats::format_bytes(max)
+        ));
+    }
+
+    if let Some(max) = testing_data
+        .max_cpu_time
+        .filter(|&max| usage.cpu_time() > max)
+    {
+        return Some(format!(
+            "Программа использовала слишком много процессорного времени\nВремя процессора: {}, допустимо не более {}",
+            stats::format_duration(usage.cpu_time()),
+            stats::format_duration(max)
+        ));
+    }
+
+    None
+}
+
+#[inline]
+fn step_failure(index: usize, rule: &RuleData, error_message: &str) -> String {
+    format!("Шаг №{} («{}»): {}", index + 1, rule.name, error_message)
+}
+
+fn failure_text(failure: &FailureReport) -> String {
+    let mut contents = format!(
+        "{}\n{}\nЗерно запуска: {}",
+        &failure.history, &failure.error_message, failure.seed
+    );
+
+    if let Some(usage) = failure.usage.as_ref() {
+        contents += &format!("\nРесурсы: {}", usage);
+    }
+
+    if let Some(minimized) = failure.minimized.as_ref() {
+        contents += &format!(
+            "\n\n#== Минимизированный пример ==#\n{}\n{}",
+            &minimized.history, &minimized.error_message
+        );
+    }
+
+    contents
+}
+
+fn save_failures(texts: Vec<String>) {
+    if !texts.is_empty() {
+        save_to_file("Ошибки", &texts.join(REPORT_SEPARATOR));
+    }
+}
+
+const REPORT_SEPARATOR: &str = "\n#====================#\n";
+
+/// Gives every run its own seed, so that any of them can be repeated alone.
+#[inline]
+fn run_seed(master_seed: u64, index: u32) -> u64 {
+    // splitmix64
+    let mut z = master_seed.wrapping_add((index as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
 }
 
 fn save_to_file(file_prefix: &str, contents: &str) {
@@ -145,64 +984,218 @@ fn save_to_file(file_prefix: &str, contents: &str) {
     }
 }
 
-impl RuleData {
-    fn to_rule(&self) -> anyhow::Result<Box<dyn Rule>> {
-        match self.content_type {
-            ContentType::PlainText => PlainText::parse(&self.text).map(|rule| {
-                let boxed: Box<dyn Rule> = Box::new(rule);
+fn parse_rule(content_type: ContentType, text: &str) -> anyhow::Result<Box<dyn Rule>> {
+    match content_type {
+        ContentType::PlainText => PlainText::parse(text).map(|rule| {
+            let boxed: Box<dyn Rule> = Box::new(rule);
 
-                boxed
-            }),
-            ContentType::Regex => RegExpr::parse(&self.text).map(|rule| {
-                let boxed: Box<dyn Rule> = Box::new(rule);
+            boxed
+        }),
+        ContentType::Regex => RegExpr::parse(text).map(|rule| {
+            let boxed: Box<dyn Rule> = Box::new(rule);
 
-                boxed
-            }),
-            ContentType::IntRanges => IntRanges::parse(&self.text).map(|rule| {
-                let boxed: Box<dyn Rule> = Box::new(rule);
+            boxed
+        }),
+        ContentType::IntRanges => IntRanges::parse(text).map(|rule| {
+            let boxed: Box<dyn Rule> = Box::new(rule);
 
-                boxed
-            }),
-        }
+            boxed
+        }),
+    }
+}
+
+impl RuleData {
+    #[inline]
+    fn to_rule(&self) -> anyhow::Result<Box<dyn Rule>> {
+        parse_rule(self.content_type, &self.text)
+    }
+}
+
+impl ArgData {
+    #[inline]
+    fn to_rule(&self) -> anyhow::Result<Box<dyn Rule>> {
+        parse_rule(self.content_type, &self.text)
     }
 }
 
 #[derive(Debug)]
 pub enum Operation {
     Output(Box<dyn Rule>),
+    Prompt(Box<dyn Rule>),
+    ErrOutput(Box<dyn Rule>),
+    ErrPrompt(Box<dyn Rule>),
     Input(Box<dyn Rule>),
+    CloseInput,
+    SendSignal(i32),
 }
 
-impl Operation {
-    #[inline]
-    fn process(rules: &[RuleData]) -> anyhow::Result<Vec<Self>> {
-        rules
+#[derive(Debug)]
+pub struct Scenario {
+    arguments: Vec<Box<dyn Rule>>,
+    operations: Vec<Operation>,
+    exit: Option<ExitExpectation>,
+}
+
+impl Scenario {
+    /// Death by a signal is a crash, unless the scenario expects that very
+    /// signal or has sent it.
+    fn failure_kind(&self, status: Option<ExitStatus>) -> FailureKind {
+        let expected = |signal| {
+            self.exit
+                .as_ref()
+                .is_some_and(|exit| exit.expects_signal(signal))
+        };
+        let sent = |signal| {
+            self.operations
+                .iter()
+                .any(|op| matches!(op, Operation::SendSignal(sent) if *sent == signal))
+        };
+
+        match status.and_then(exit_status::signal) {
+            Some(signal) if !expected(signal) && !sent(signal) => FailureKind::Crashed,
+            _ => FailureKind::Failed,
+        }
+    }
+
+    pub fn process(rules: &[RuleData], args: &[ArgData]) -> anyhow::Result<Self> {
+        let arguments = args
             .iter()
-            .map(|rule| {
-                Ok(match rule.rule_type {
-                    RuleType::Input => Self::Input(rule.to_rule()?),
-                    RuleType::Output => Self::Output(rule.to_rule()?),
-                })
-            })
-            .collect()
+            .map(ArgData::to_rule)
+            .collect::<anyhow::Result<_>>()?;
+
+        let mut operations = Vec::with_capacity(rules.len());
+        let mut exit = None;
+        let mut input_closed = false;
+
+        for rule in rules.iter() {
+            if exit.is_some() {
+                anyhow::bail!(
+                    "Правило «{}»: проверка завершения программы должна быть последним шагом",
+                    rule.name
+                );
+            }
+
+            match rule.rule_type {
+                RuleType::Input if input_closed => {
+                    anyhow::bail!("Правило «{}»: ввод уже закрыт", rule.name);
+                }
+                RuleType::Input => operations.push(Operation::Input(rule.to_rule()?)),
+                RuleType::Output => operations.push(Operation::Output(rule.to_rule()?)),
+                RuleType::Prompt => operations.push(Operation::Prompt(rule.to_rule()?)),
+                RuleType::ErrOutput => operations.push(Operation::ErrOutput(rule.to_rule()?)),
+                RuleType::ErrPrompt => operations.push(Operation::ErrPrompt(rule.to_rule()?)),
+                RuleType::ExitCode | RuleType::Signal => {
+                    exit = Some(ExitExpectation::parse(rule)?);
+                }
+                RuleType::CloseInput => {
+                    input_closed = true;
+                    operations.push(Operation::CloseInput);
+                }
+                RuleType::SendSignal => {
+                    let signal = exit_status::parse_signal(&rule.text).map_err(|error| {
+                        anyhow::format_err!("Правило «{}»: {}", rule.name, error)
+                    })?;
+
+                    operations.push(Operation::SendSignal(signal));
+                }
+            }
+        }
+
+        Ok(Self {
+            arguments,
+            operations,
+            exit,
+        })
     }
+}
 
-    fn exec(&self, comm: &mut Communicator) -> anyhow::Result<OpReport> {
+impl Operation {
+    pub fn exec(&self, comm: &mut Communicator, choices: &mut Choices) -> anyhow::Result<OpReport> {
         match self {
             Self::Input(rule) => {
-                let string = rule.generate()?;
+                let string = rule.generate_from(choices)?;
+
+                // the program would get a different line than the history shows
+                if let Some(max) = comm.max_input_line_len() {
+                    if string.len() > max {
+                        return Ok(OpReport::Failure {
+                            error_message: format!(
+                                "Строка ввода длиной {} байт не помещается в буфер терминала ({} байт): отключите режим терминала",
+                                string.len(),
+                                max
+                            ),
+                        });
+                    }
+                }
+
+                if let Some(end) = comm.input_line_end() {
+                    if string.contains(&end) {
+                        return Ok(OpReport::Failure {
+                            error_message: format!(
+                                "Строка ввода содержит байт {:#04x}, который терминал принимает за конец ввода: отключите режим терминала",
+                                end
+                            ),
+                        });
+                    }
+                }
 
                 comm.write_line(string)?;
 
                 Ok(OpReport::Success)
             }
-            Self::Output(rule) => {
-                let text = comm.read_line()?;
+            Self::Output(rule) => Self::check_output(comm, Stream::Stdout, rule.as_ref()),
+            Self::ErrOutput(rule) => Self::check_output(comm, Stream::Stderr, rule.as_ref()),
+            Self::Prompt(rule) => Self::check_prompt(comm, Stream::Stdout, rule.as_ref()),
+            Self::ErrPrompt(rule) => Self::check_prompt(comm, Stream::Stderr, rule.as_ref()),
+            Self::CloseInput => {
+                comm.close_input();
+
+                Ok(OpReport::Success)
+            }
+            Self::SendSignal(signal) => {
+                comm.send_signal(*signal)?;
 
-                Ok(rule.validate(&text))
+                Ok(OpReport::Success)
             }
         }
     }
+
+    #[inline]
+    fn check_output(
+        comm: &mut Communicator,
+        stream: Stream,
+        rule: &dyn Rule,
+    ) -> anyhow::Result<OpReport> {
+        Self::check_read(comm.read_line(stream)?, rule)
+    }
+
+    #[inline]
+    fn check_prompt(
+        comm: &mut Communicator,
+        stream: Stream,
+        rule: &dyn Rule,
+    ) -> anyhow::Result<OpReport> {
+        let result = comm.read_prompt(stream, |text| {
+            matches!(rule.validate(text), OpReport::Success)
+        })?;
+
+        Self::check_read(result, rule)
+    }
+
+    fn check_read(result: ReadResult, rule: &dyn Rule) -> anyhow::Result<OpReport> {
+        match result {
+            ReadResult::Line(text) => Ok(rule.validate(&text)),
+            ReadResult::Eof => Ok(OpReport::Failure {
+                error_message: "Программа завершила вывод раньше времени".to_owned(),
+            }),
+            ReadResult::TimedOut(kind) => Ok(OpReport::Failure {
+                error_message: kind.to_string(),
+            }),
+            ReadResult::OutputLimit(limit) => Ok(OpReport::Failure {
+                error_message: limit.to_string(),
+            }),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -210,3 +1203,737 @@ pub enum OpReport {
     Success,
     Failure { error_message: String },
 }
+
+//===================================================================================//
+//===================================// TESTING //===================================//
+//===================================================================================//
+
+#[cfg(all(test, unix))]
+mod test_util {
+    use super::{RunOutcome, Scenario, Tester};
+    use crate::{
+        rule_data::{ArgData, ContentType, RuleData, RuleType},
+        run_manager::{LaunchSettings, TestingData},
+    };
+    use std::sync::Arc;
+
+    /// Runs `script` with `sh -c`.
+    pub(super) fn shell_testing_data(script: &str, rules: Vec<RuleData>) -> TestingData {
+        let launch = LaunchSettings {
+            args: ["-c", script]
+                .into_iter()
+                .map(|arg| ArgData {
+                    content_type: ContentType::PlainText,
+                    text: arg.to_owned(),
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        TestingData {
+            program_path: "sh".into(),
+            rules,
+            successes_required: 1,
+            workers: 1,
+            continue_on_failure: false,
+            time_budget: None,
+            max_run_duration: None,
+            max_peak_memory: None,
+            max_cpu_time: None,
+            comm_settings: Default::default(),
+            launch,
+            master_seed: None,
+            replay_seed: None,
+        }
+    }
+
+    /// Runs the program once, with seed 0.
+    pub(super) fn run_once(testing_data: &TestingData) -> RunOutcome {
+        let scenario = Scenario::process(&testing_data.rules, &testing_data.launch.args).unwrap();
+
+        Tester::new(Arc::default())
+            .run_single(testing_data, &scenario, 0, None)
+            .unwrap()
+    }
+
+    /// A plain text step, named after its type.
+    pub(super) fn rule(rule_type: RuleType, text: &str) -> RuleData {
+        RuleData {
+            name: format!("{}", rule_type),
+            rule_type,
+            content_type: ContentType::PlainText,
+            text: text.to_owned(),
+        }
+    }
+
+    pub(super) fn output(text: &str) -> RuleData {
+        rule(RuleType::Output, text)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_minimize {
+    use super::{test_util::shell_testing_data, Scenario, Tester};
+    use crate::rule_data::{ContentType, RuleData, RuleType};
+    use std::{sync::Arc, time::Instant};
+
+    /// Minimizes the first failure without saving it to a file.
+    fn minimized_history(
+        script: &str,
+        content_type: ContentType,
+        text: &str,
+        deadline: Option<Instant>,
+    ) -> Option<String> {
+        let mut tester = Tester::new(Arc::default());
+
+        let rules = vec![RuleData {
+            name: "input".to_owned(),
+            rule_type: RuleType::Input,
+            content_type,
+            text: text.to_owned(),
+        }];
+        let testing_data = shell_testing_data(script, rules);
+        let scenario = Scenario::process(&testing_data.rules, &testing_data.launch.args).unwrap();
+
+        for seed in 0..100 {
+            let outcome = tester
+                .run_single(&testing_data, &scenario, seed, None)
+                .unwrap();
+
+            if let Some(failure) = outcome.failure {
+                let minimized = tester
+                    .minimize(
+                        &testing_data,
+                        &scenario,
+                        seed,
+                        &failure.signature,
+                        outcome.picks,
+                        deadline,
+                    )
+                    .unwrap();
+
+                return minimized.map(|minimized| minimized.history.to_string());
+            }
+        }
+
+        panic!("no failures found");
+    }
+
+    #[test]
+    fn smallest_failing_int() {
+        let history = minimized_history(
+            "read n; [ $n -le 100 ]",
+            ContentType::IntRanges,
+            "0..100000",
+            None,
+        );
+
+        assert!(history.unwrap().ends_with("\n> 101\n\n"));
+    }
+
+    #[test]
+    fn shortest_failing_string() {
+        let history = minimized_history(
+            "read s; [ ${#s} -lt 5 ]",
+            ContentType::Regex,
+            "[a-z]{1,20}",
+            None,
+        );
+
+        assert!(history.unwrap().ends_with("\n> aaaaa\n\n"));
+    }
+
+    #[test]
+    fn session_over() {
+        let history = minimized_history(
+            "read n; [ $n -le 100 ]",
+            ContentType::IntRanges,
+            "0..100000",
+            Some(Instant::now()),
+        );
+
+        assert_eq!(history, None);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_failure_kind {
+    use super::test_util::{output, run_once, shell_testing_data};
+    use crate::{
+        communicator::CommSettings,
+        rule_data::{RuleData, RuleType},
+        run_manager::FailureKind,
+    };
+    use std::time::Duration;
+
+    fn kind(script: &str, rules: Vec<RuleData>) -> Option<FailureKind> {
+        let mut testing_data = shell_testing_data(script, rules);
+        testing_data.comm_settings = CommSettings {
+            read_timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+
+        run_once(&testing_data).failure.map(|failure| failure.kind)
+    }
+
+    #[test]
+    fn passed() {
+        assert_eq!(kind("echo hi", vec![output("hi")]), None);
+    }
+
+    #[test]
+    fn wrong_output() {
+        assert_eq!(
+            kind("echo bye", vec![output("hi")]),
+            Some(FailureKind::Failed)
+        );
+    }
+
+    #[test]
+    fn exit_code() {
+        assert_eq!(kind("exit 3", vec![]), Some(FailureKind::Failed));
+    }
+
+    #[test]
+    fn crash_at_exit() {
+        assert_eq!(kind("kill -SEGV $$", vec![]), Some(FailureKind::Crashed));
+    }
+
+    #[test]
+    fn crash_mid_output() {
+        assert_eq!(
+            kind("echo hi; kill -SEGV $$", vec![output("hi"), output("more")]),
+            Some(FailureKind::Crashed)
+        );
+    }
+
+    #[test]
+    fn expected_signal() {
+        let signal = |text: &str| RuleData {
+            rule_type: RuleType::Signal,
+            text: text.to_owned(),
+            ..Default::default()
+        };
+
+        // the right signal after the wrong output
+        assert_eq!(
+            kind("echo bye; kill -KILL $$", vec![output("hi"), signal("9")]),
+            Some(FailureKind::Failed)
+        );
+        assert_eq!(
+            kind("echo bye; kill -SEGV $$", vec![output("hi"), signal("")]),
+            Some(FailureKind::Failed)
+        );
+        // some other signal
+        assert_eq!(
+            kind("kill -SEGV $$", vec![signal("9")]),
+            Some(FailureKind::Crashed)
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        assert_eq!(
+            kind("sleep 10", vec![output("hi")]),
+            Some(FailureKind::TimedOut)
+        );
+    }
+
+    #[test]
+    fn endless_output() {
+        // killed by the tester => not a crash
+        assert_eq!(kind("yes", vec![]), Some(FailureKind::Failed));
+        assert_eq!(kind("yes", vec![output("y")]), Some(FailureKind::Failed));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_crash_reports {
+    use super::{
+        test_util::{output, run_once, shell_testing_data},
+        RunFailure,
+    };
+    use crate::{rule_data::RuleData, run_manager::FailureKind};
+
+    fn failure(script: &str, rules: Vec<RuleData>) -> RunFailure {
+        run_once(&shell_testing_data(script, rules))
+            .failure
+            .unwrap()
+    }
+
+    #[test]
+    fn signal_by_name() {
+        let failure = failure("kill -SEGV $$", vec![]);
+
+        assert_eq!(failure.kind, FailureKind::Crashed);
+        assert!(failure
+            .error_message
+            .contains("SIGSEGV (11, нарушение доступа к памяти)"));
+    }
+
+    #[test]
+    fn signal_mid_output() {
+        let failure = failure("echo hi; kill -FPE $$", vec![output("hi"), output("more")]);
+
+        assert!(failure
+            .error_message
+            .contains("\nСтатус завершения: завершена сигналом SIGFPE"));
+    }
+
+    #[test]
+    fn undefined_behavior_after_success() {
+        let failure = failure(
+            "echo 'a.c:5:13: runtime error: signed integer overflow: 1 + 2147483647' >&2",
+            vec![],
+        );
+
+        assert_eq!(failure.kind, FailureKind::Failed);
+        assert_eq!(
+            failure.signature.message_kind,
+            "UndefinedBehaviorSanitizer: signed integer overflow (a.c:5:13)"
+        );
+        assert!(failure.error_message.starts_with(
+            "UndefinedBehaviorSanitizer: signed integer overflow (a.c:5:13)\nСанитайзер"
+        ));
+    }
+
+    #[test]
+    fn address_sanitizer_abort() {
+        let script = "echo '==1==ERROR: AddressSanitizer: SEGV on unknown address 0x0' >&2; \
+                      echo 'SUMMARY: AddressSanitizer: SEGV /src/a.c:3 in main' >&2; exit 1";
+        let failure = failure(script, vec![output("hi")]);
+
+        assert_eq!(
+            failure.signature.message_kind,
+            "AddressSanitizer: SEGV /src/a.c:3 in main"
+        );
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_wrapper {
+    use super::{
+        test_util::{output, run_once, shell_testing_data},
+        RunOutcome, Tester,
+    };
+    use crate::run_manager::{FailureKind, TestReport, Wrapper};
+    use std::sync::Arc;
+
+    /// Runs `script` under `env WRAPPED=yes`, with exit code 99 for memory errors.
+    fn run(script: &str) -> RunOutcome {
+        let mut testing_data = shell_testing_data(script, vec![output("yes")]);
+        testing_data.launch.wrapper = Some(Wrapper {
+            command: Wrapper::split_command("env WRAPPED=yes"),
+            exit_codes: vec![(99, "Ошибка памяти (valgrind)".to_owned())],
+            ..Default::default()
+        });
+
+        run_once(&testing_data)
+    }
+
+    #[test]
+    fn runs_under_wrapper() {
+        let outcome = run("echo $WRAPPED");
+
+        assert!(outcome.failure.is_none(), "{:?}", outcome.failure);
+    }
+
+    #[test]
+    fn mapped_exit_code() {
+        let failure = run("echo $WRAPPED; exit 99").failure.unwrap();
+
+        assert_eq!(failure.kind, FailureKind::Failed);
+        assert_eq!(failure.signature.message_kind, "Ошибка памяти (valgrind)");
+        assert!(failure
+            .error_message
+            .starts_with("Ошибка памяти (valgrind)\n"));
+    }
+
+    #[test]
+    fn other_exit_code() {
+        let failure = run("echo $WRAPPED; exit 1").failure.unwrap();
+
+        assert!(!failure.error_message.contains("valgrind"));
+    }
+
+    #[test]
+    fn limits_for_wrapper() {
+        for (limit_address_space, address_space) in [(false, "unlimited"), (true, "1048576")] {
+            let script = format!("[ $(ulimit -n) = 16 ] && [ $(ulimit -v) = {address_space} ]");
+            let mut testing_data = shell_testing_data(&script, vec![]);
+            testing_data.launch.limits.open_files = Some(16);
+            testing_data.launch.limits.address_space = Some(1 << 30);
+            testing_data.launch.wrapper = Some(Wrapper {
+                command: Wrapper::split_command("env"),
+                limit_address_space,
+                ..Default::default()
+            });
+
+            let outcome = run_once(&testing_data);
+
+            assert!(outcome.failure.is_none(), "{:?}", outcome.failure);
+        }
+    }
+
+    #[test]
+    fn empty_wrapper() {
+        let mut testing_data = shell_testing_data("true", vec![]);
+        testing_data.launch.wrapper = Some(Wrapper::default());
+
+        assert!(matches!(
+            Tester::new(Arc::default()).run(testing_data),
+            TestReport::Error(_)
+        ));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_scenario_steps {
+    use super::{
+        test_util::{rule, run_once, shell_testing_data},
+        RunOutcome, Scenario,
+    };
+    use crate::{
+        rule_data::{RuleData, RuleType},
+        run_manager::FailureKind,
+    };
+
+    fn run(script: &str, rules: Vec<RuleData>) -> RunOutcome {
+        run_once(&shell_testing_data(script, rules))
+    }
+
+    #[test]
+    fn summary_after_end_of_input() {
+        let outcome = run(
+            "n=0; while read x; do n=$((n + x)); done; echo \"sum $n\"",
+            vec![
+                rule(RuleType::Input, "1"),
+                rule(RuleType::Input, "2"),
+                rule(RuleType::CloseInput, ""),
+                rule(RuleType::Output, "sum 3"),
+            ],
+        );
+
+        assert!(outcome.failure.is_none(), "{:?}", outcome.failure);
+        assert!(outcome.history.to_string().contains("\n* ввод закрыт\n"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn line_too_long_for_terminal() {
+        let mut testing_data =
+            shell_testing_data("cat", vec![rule(RuleType::Input, &"x".repeat(5000))]);
+        testing_data.comm_settings.terminal = true;
+
+        let failure = run_once(&testing_data).failure.unwrap();
+        assert_eq!(failure.kind, FailureKind::Failed);
+        assert!(failure
+            .error_message
+            .contains("не помещается в буфер терминала"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn end_of_input_byte_for_terminal() {
+        let mut testing_data = shell_testing_data("cat", vec![rule(RuleType::Input, "pq\x04rs")]);
+        testing_data.comm_settings.terminal = true;
+
+        let failure = run_once(&testing_data).failure.unwrap();
+        assert_eq!(failure.kind, FailureKind::Failed);
+        assert!(failure.error_message.contains("0x04"));
+    }
+
+    #[test]
+    fn prompt_on_stderr() {
+        let outcome = run(
+            "printf 'N: ' >&2; read n; echo $((n + 1))",
+            vec![
+                rule(RuleType::ErrPrompt, "N: "),
+                rule(RuleType::Input, "1"),
+                rule(RuleType::Output, "2"),
+            ],
+        );
+
+        assert!(outcome.failure.is_none(), "{:?}", outcome.failure);
+    }
+
+    #[test]
+    fn handled_interrupt() {
+        let outcome = run(
+            "trap 'echo bye; exit 0' INT; echo ready; while :; do sleep 0.05; done",
+            vec![
+                rule(RuleType::Output, "ready"),
+                rule(RuleType::SendSignal, "SIGINT"),
+                rule(RuleType::Output, "bye"),
+                rule(RuleType::ExitCode, "0"),
+            ],
+        );
+
+        assert!(outcome.failure.is_none(), "{:?}", outcome.failure);
+        assert!(outcome
+            .history
+            .to_string()
+            .contains("\n* отправлен сигнал SIGINT\n"));
+    }
+
+    #[test]
+    fn killed_by_sent_signal() {
+        let outcome = run(
+            "echo ready; sleep 10",
+            vec![
+                rule(RuleType::Output, "ready"),
+                rule(RuleType::SendSignal, "TERM"),
+            ],
+        );
+
+        // the program didn't handle it, but it didn't crash either
+        assert_eq!(outcome.failure.unwrap().kind, FailureKind::Failed);
+    }
+
+    #[test]
+    fn invalid_steps() {
+        for rules in [
+            vec![rule(RuleType::CloseInput, ""), rule(RuleType::Input, "1")],
+            vec![rule(RuleType::SendSignal, "KILL")],
+        ] {
+            assert!(Scenario::process(&rules, &[]).is_err(), "{rules:?}");
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_failure_groups {
+    use super::{group_failures, test_util::shell_testing_data, FailedRun, Scenario, Tester};
+    use crate::{
+        rule_data::{ContentType, RuleData, RuleType},
+        run_manager::TestingData,
+    };
+    use std::sync::Arc;
+
+    /// Numbers from 50 exit with code 1, numbers from 80 with code 2.
+    const SCRIPT: &str = "read n; [ $n -lt 50 ] || exit $(( n < 80 ? 1 : 2 ))";
+
+    fn testing_data() -> (TestingData, Scenario) {
+        let rules = vec![RuleData {
+            name: "n".to_owned(),
+            rule_type: RuleType::Input,
+            content_type: ContentType::IntRanges,
+            text: "0..99".to_owned(),
+        }];
+        let testing_data = shell_testing_data(SCRIPT, rules);
+        let scenario = Scenario::process(&testing_data.rules, &testing_data.launch.args).unwrap();
+
+        (testing_data, scenario)
+    }
+
+    fn failed_runs(
+        tester: &Tester,
+        testing_data: &TestingData,
+        scenario: &Scenario,
+    ) -> Vec<FailedRun> {
+        (0..60)
+            .map(|index| {
+                let seed = index as u64;
+                let outcome = tester
+                    .run_single(testing_data, scenario, seed, None)
+                    .unwrap();
+
+                FailedRun {
+                    index,
+                    seed,
+                    outcome,
+                }
+            })
+            .filter(|failed| failed.outcome.failure.is_some())
+            .collect()
+    }
+
+    #[test]
+    fn same_exit_same_group() {
+        let tester = Tester::new(Arc::default());
+        let (testing_data, scenario) = testing_data();
+        let failures = failed_runs(&tester, &testing_data, &scenario);
+        let total = failures.len() as u32;
+
+        let groups = group_failures(failures);
+
+        assert_eq!(groups.len(), 2);
+        assert!(groups[0].0 >= groups[1].0);
+        assert_eq!(groups.iter().map(|(count, _)| count).sum::<u32>(), total);
+        assert_ne!(
+            groups[0].1.failure().signature,
+            groups[1].1.failure().signature
+        );
+    }
+
+    #[test]
+    fn minimizing_keeps_signature() {
+        let mut tester = Tester::new(Arc::default());
+        let (testing_data, scenario) = testing_data();
+        let failures = failed_runs(&tester, &testing_data, &scenario);
+
+        let failed = failures
+            .into_iter()
+            .find(|failed| failed.failure().signature.exit.as_deref() == Some("код завершения 2"))
+            .expect("no run with exit code 2");
+
+        let minimized = tester
+            .minimize(
+                &testing_data,
+                &scenario,
+                failed.seed,
+                &failed.failure().signature,
+                failed.outcome.picks.clone(),
+                None,
+            )
+            .unwrap()
+            .unwrap();
+
+        // 50 fails too, but with a different exit code
+        assert!(minimized.history.to_string().ends_with("\n> 80\n\n"));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_timing {
+    use super::{
+        test_util::{output, run_once, shell_testing_data},
+        RunOutcome, Scenario, Tester,
+    };
+    use crate::{rule_data::RuleData, run_manager::TestingData};
+    use std::{sync::Arc, time::Duration};
+
+    fn run(script: &str, rules: Vec<RuleData>, max: Option<Duration>) -> RunOutcome {
+        let mut testing_data = shell_testing_data(script, rules);
+        testing_data.max_run_duration = max;
+
+        run_once(&testing_data)
+    }
+
+    #[test]
+    fn durations() {
+        let outcome = run(
+            "sleep 0.2; echo a; echo b; sleep 0.1",
+            vec![output("a"), output("b")],
+            None,
+        );
+
+        assert!(outcome.failure.is_none());
+        assert!(outcome.duration.unwrap() >= Duration::from_millis(300));
+        assert_eq!(outcome.output_waits.len(), 2);
+        assert!(outcome.output_waits[0] >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn slow_run_fails() {
+        let outcome = run("sleep 0.2", vec![], Some(Duration::from_millis(100)));
+
+        let failure = outcome.failure.unwrap();
+        assert_eq!(
+            failure.signature.message_kind,
+            "Программа работала слишком долго"
+        );
+    }
+
+    #[test]
+    fn fast_run_passes() {
+        let outcome = run("true", vec![], Some(Duration::from_secs(5)));
+
+        assert!(outcome.failure.is_none());
+    }
+
+    #[test]
+    fn resource_usage() {
+        let outcome = run(
+            "i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done",
+            vec![],
+            None,
+        );
+
+        let usage = outcome.usage.unwrap();
+        assert!(usage.peak_memory > 0);
+        assert!(usage.cpu_time() > Duration::ZERO);
+    }
+
+    #[test]
+    fn resource_limits() {
+        let mut testing_data =
+            shell_testing_data("i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done", vec![]);
+        let scenario = Scenario::process(&testing_data.rules, &testing_data.launch.args).unwrap();
+        let tester = Tester::new(Arc::default());
+
+        let message_kind = |testing_data: &TestingData| {
+            tester
+                .run_single(testing_data, &scenario, 0, None)
+                .unwrap()
+                .failure
+                .map(|failure| failure.signature.message_kind)
+        };
+
+        testing_data.max_peak_memory = Some(1 << 40);
+        testing_data.max_cpu_time = Some(Duration::from_secs(60));
+        assert_eq!(message_kind(&testing_data), None);
+
+        testing_data.max_cpu_time = Some(Duration::from_nanos(1));
+        assert_eq!(
+            message_kind(&testing_data).unwrap(),
+            "Программа использовала слишком много процессорного времени"
+        );
+
+        testing_data.max_peak_memory = Some(1);
+        assert_eq!(
+            message_kind(&testing_data).unwrap(),
+            "Программа использовала слишком много памяти"
+        );
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test_limits {
+    use super::{
+        test_util::{output, run_once, shell_testing_data},
+        RunFailure,
+    };
+    use crate::{limits::ResourceLimits, rule_data::RuleData, run_manager::FailureKind};
+
+    fn run(script: &str, rules: Vec<RuleData>, limits: ResourceLimits) -> Option<RunFailure> {
+        let mut testing_data = shell_testing_data(script, rules);
+        testing_data.launch.limits = limits;
+
+        run_once(&testing_data).failure
+    }
+
+    #[test]
+    fn applied() {
+        let limits = ResourceLimits {
+            address_space: Some(512 << 20),
+            open_files: Some(17),
+            ..Default::default()
+        };
+
+        // `ulimit -v` is in kibibytes
+        let failure = run(
+            "ulimit -n; ulimit -v",
+            vec![output("17"), output("524288")],
+            limits,
+        );
+
+        assert!(failure.is_none());
+    }
+
+    #[test]
+    fn cpu_time() {
+        let limits = ResourceLimits {
+            cpu_time: Some(1),
+            ..Default::default()
+        };
+
+        let failure = run("while :; do :; done", vec![], limits).unwrap();
+
+        assert_eq!(failure.kind, FailureKind::Crashed);
+        assert_eq!(
+            failure.signature.message_kind,
+            "Превышен лимит процессорного времени (1 с)"
+        );
+    }
+}